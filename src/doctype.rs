@@ -0,0 +1,169 @@
+use parser::{check_internal_subset, is_xml_char};
+use {is_valid_name, XMLError};
+
+/// A document type declaration, such as
+/// `<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "...">`.
+///
+/// ```rust
+/// use simple_xml_builder::XMLDoctype;
+///
+/// let doctype = XMLDoctype::new("plist").public_id(
+///     "-//Apple//DTD PLIST 1.0//EN",
+///     "http://www.apple.com/DTDs/PropertyList-1.0.dtd",
+/// );
+///
+/// let mut doctype = XMLDoctype::new("doc");
+/// doctype.add_entity("product", "Simple XML Builder");
+/// doctype.add_raw("<!ELEMENT doc (#PCDATA)>");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct XMLDoctype {
+    name: String,
+    external_id: Option<ExternalId>,
+    internal_subset: Vec<Declaration>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum ExternalId {
+    System(String),
+    Public(String, String),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Declaration {
+    Entity(String, String),
+    Raw(String),
+}
+
+impl XMLDoctype {
+    /// Creates a document type declaration for the given root element name,
+    /// with no external identifier or internal subset.
+    pub fn new(name: impl ToString) -> Self {
+        XMLDoctype {
+            name: name.to_string(),
+            external_id: None,
+            internal_subset: Vec::new(),
+        }
+    }
+
+    /// Sets a `SYSTEM` external identifier.
+    pub fn system_id(mut self, system_id: impl ToString) -> Self {
+        self.external_id = Some(ExternalId::System(system_id.to_string()));
+        self
+    }
+
+    /// Sets a `PUBLIC` external identifier.
+    pub fn public_id(mut self, public_id: impl ToString, system_id: impl ToString) -> Self {
+        self.external_id = Some(ExternalId::Public(
+            public_id.to_string(),
+            system_id.to_string(),
+        ));
+        self
+    }
+
+    /// Adds an internal general entity declaration to the internal subset.
+    ///
+    /// The value is written as an entity value literal, with `"` and `%`
+    /// written as character references. Other references in the value are
+    /// kept, and expanded by parsers, so each `&` must start a reference. A
+    /// literal `&` can be written as `&#38;#38;`.
+    pub fn add_entity(&mut self, name: impl ToString, value: impl ToString) {
+        self.internal_subset
+            .push(Declaration::Entity(name.to_string(), value.to_string()));
+    }
+
+    /// Adds markup declarations to the internal subset, written as is. They
+    /// must be well-formed for the document type declaration to be written.
+    pub fn add_raw(&mut self, declarations: impl ToString) {
+        self.internal_subset
+            .push(Declaration::Raw(declarations.to_string()));
+    }
+
+    /// Checks that the declaration can be written as well-formed XML.
+    pub(crate) fn check(&self) -> Result<(), XMLError> {
+        let valid = is_valid_name(&self.name)
+            && match &self.external_id {
+                Some(ExternalId::Public(public_id, system_id)) => {
+                    public_id.chars().all(is_pubid_char) && is_system_literal(system_id)
+                }
+                Some(ExternalId::System(system_id)) => is_system_literal(system_id),
+                None => true,
+            }
+            && self.internal_subset.iter().all(|decl| match decl {
+                Declaration::Entity(name, value) => {
+                    is_valid_name(name)
+                        && value.chars().all(is_xml_char)
+                        && has_valid_references(value)
+                }
+                Declaration::Raw(raw) => check_internal_subset(raw).is_ok(),
+            });
+        if valid {
+            Ok(())
+        } else {
+            Err(XMLError::InvalidDoctype {
+                name: self.name.clone(),
+            })
+        }
+    }
+
+    /// Returns the start of the declaration, up to the internal subset.
+    pub(crate) fn header(&self) -> String {
+        let mut result = format!("<!DOCTYPE {}", self.name);
+        match &self.external_id {
+            Some(ExternalId::System(system_id)) => {
+                result += &format!(" SYSTEM {}", quote(system_id));
+            }
+            Some(ExternalId::Public(public_id, system_id)) => {
+                result += &format!(r#" PUBLIC "{}" {}"#, public_id, quote(system_id));
+            }
+            None => {}
+        }
+        result
+    }
+
+    /// Returns the markup declarations in the internal subset.
+    pub(crate) fn internal_subset(&self) -> impl Iterator<Item = String> + '_ {
+        self.internal_subset.iter().map(|decl| match decl {
+            Declaration::Entity(name, value) => format!(
+                r#"<!ENTITY {} "{}">"#,
+                name,
+                value.replace('"', "&#34;").replace('%', "&#37;")
+            ),
+            Declaration::Raw(raw) => raw.clone(),
+        })
+    }
+}
+
+/// Returns whether `c` matches the `PubidChar` production.
+fn is_pubid_char(c: char) -> bool {
+    matches!(c, ' ' | '\r' | '\n' | 'a'..='z' | 'A'..='Z' | '0'..='9')
+        || "-'()+,./:=?;!*#@$_%".contains(c)
+}
+
+/// Returns whether every `&` in an entity value starts a well-formed entity
+/// or character reference.
+fn has_valid_references(value: &str) -> bool {
+    value.split('&').skip(1).all(|rest| match rest.find(';') {
+        Some(end) => match rest[..end].strip_prefix('#') {
+            Some(code) => match code.strip_prefix('x') {
+                Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+                None => !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()),
+            },
+            None => is_valid_name(&rest[..end]),
+        },
+        None => false,
+    })
+}
+
+fn is_system_literal(literal: &str) -> bool {
+    !(literal.contains('"') && literal.contains('\'')) && literal.chars().all(is_xml_char)
+}
+
+/// Quotes a system literal, using single quotes if it contains double quotes.
+fn quote(literal: &str) -> String {
+    if literal.contains('"') {
+        format!("'{}'", literal)
+    } else {
+        format!(r#""{}""#, literal)
+    }
+}
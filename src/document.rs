@@ -0,0 +1,312 @@
+use parser::{decode_input, parse_document};
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use {
+    check_comment, check_processing_instruction, ParseOptions, WriteOptions, XMLDeclaration,
+    XMLDoctype, XMLElement, XMLError, XMLNode, XMLWriter,
+};
+
+/// Represents an XML document: a root element together with the XML
+/// declaration, document type declaration, and comments and processing
+/// instructions before and after the root element.
+///
+/// # Example
+///
+/// ```rust
+/// use simple_xml_builder::{XMLDoctype, XMLDocument, XMLElement, XMLNode};
+///
+/// let mut doc = XMLDocument::new(XMLElement::new("html"));
+/// doc.set_doctype(Some(XMLDoctype::new("html")));
+/// doc.add_prolog(XMLNode::ProcessingInstruction {
+///     target: "xml-stylesheet".to_owned(),
+///     data: r#"type="text/xsl" href="style.xsl""#.to_owned(),
+/// })
+/// .unwrap();
+/// doc.add_epilog(XMLNode::Comment(" end ".to_owned())).unwrap();
+/// ```
+/// will write:
+/// ```xml
+/// <?xml version="1.0" encoding="UTF-8"?>
+/// <!DOCTYPE html>
+/// <?xml-stylesheet type="text/xsl" href="style.xsl"?>
+/// <html />
+/// <!-- end -->
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct XMLDocument {
+    /// The XML declaration, if set, which overrides the one in
+    /// [WriteOptions].
+    declaration: Option<Option<XMLDeclaration>>,
+    doctype: Option<XMLDoctype>,
+    doctype_position: usize,
+    prolog: Vec<XMLNode>,
+    root: XMLElement,
+    epilog: Vec<XMLNode>,
+}
+
+impl XMLDocument {
+    /// Creates a new document with the given root element, written with the
+    /// XML declaration in [WriteOptions].
+    pub fn new(root: XMLElement) -> Self {
+        XMLDocument {
+            declaration: None,
+            doctype: None,
+            doctype_position: 0,
+            prolog: Vec::new(),
+            root,
+            epilog: Vec::new(),
+        }
+    }
+
+    /// Parses a document from XML text.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::Parse] if the text is not a well-formed XML document,
+    /// or uses features that are not supported, such as entity declarations.
+    pub fn from_str_with(s: &str, options: &ParseOptions) -> Result<Self, XMLError> {
+        parse_document(s, options)
+    }
+
+    /// Parses a document from a reader. The encoding is detected from a byte
+    /// order mark or the XML declaration, defaulting to UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::Parse] if the input is not a well-formed XML
+    /// document or its encoding is not supported, or [XMLError::Io] for
+    /// errors from reading.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, XMLError> {
+        Self::from_reader_with(reader, &ParseOptions::default())
+    }
+
+    /// Parses a document from a reader, like
+    /// [from_reader](XMLDocument::from_reader).
+    ///
+    /// # Errors
+    ///
+    /// Same as [from_reader](XMLDocument::from_reader).
+    pub fn from_reader_with<R: Read>(
+        mut reader: R,
+        options: &ParseOptions,
+    ) -> Result<Self, XMLError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        parse_document(&decode_input(&bytes)?, options)
+    }
+
+    /// Returns the root element.
+    pub fn root(&self) -> &XMLElement {
+        &self.root
+    }
+
+    /// Returns the root element for modification.
+    pub fn root_mut(&mut self) -> &mut XMLElement {
+        &mut self.root
+    }
+
+    /// Returns the root element, consuming the document.
+    pub fn into_root(self) -> XMLElement {
+        self.root
+    }
+
+    /// Sets the XML declaration, or leaves it out if `None`. This takes
+    /// precedence over the declaration in [WriteOptions].
+    pub fn set_declaration(&mut self, declaration: Option<XMLDeclaration>) {
+        self.declaration = Some(declaration);
+    }
+
+    /// Sets the document type declaration, or removes it if `None`. It is
+    /// written after the prolog nodes added so far.
+    pub fn set_doctype(&mut self, doctype: Option<XMLDoctype>) {
+        self.doctype = doctype;
+        self.doctype_position = self.prolog.len();
+    }
+
+    /// Appends a comment or processing instruction to the prolog, written
+    /// before the root element, and after the document type declaration if
+    /// it has been set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::ContentOutsideRoot] if the node is not a comment or
+    /// processing instruction, or [XMLError::InvalidComment] or
+    /// [XMLError::InvalidProcessingInstruction] if it is not well-formed.
+    pub fn add_prolog(&mut self, node: XMLNode) -> Result<(), XMLError> {
+        check_misc(&node)?;
+        self.prolog.push(node);
+        Ok(())
+    }
+
+    /// Appends a comment or processing instruction to the epilog, written
+    /// after the root element.
+    ///
+    /// # Errors
+    ///
+    /// Same as [add_prolog](XMLDocument::add_prolog).
+    pub fn add_epilog(&mut self, node: XMLNode) -> Result<(), XMLError> {
+        check_misc(&node)?;
+        self.epilog.push(node);
+        Ok(())
+    }
+
+    /// Outputs the UTF-8 XML document.
+    ///
+    /// Output is formatted as by [XMLElement::write].
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::Io] for errors from writing to the Write object.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), XMLError> {
+        self.write_with(writer, WriteOptions::default())
+    }
+
+    /// Outputs the XML document, formatted and encoded according to
+    /// `options`. The document's XML declaration is used instead of the one
+    /// in `options` if it was set with
+    /// [set_declaration](XMLDocument::set_declaration) or parsed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidDoctype] if the document type declaration is
+    /// not well-formed, [XMLError::Unencodable] if markup contains characters
+    /// that cannot be represented in the chosen encoding, or [XMLError::Io]
+    /// for errors from writing to the Write object.
+    pub fn write_with<W: Write>(&self, writer: W, options: WriteOptions) -> Result<(), XMLError> {
+        let options = match &self.declaration {
+            Some(declaration) => options.declaration(declaration.clone()),
+            None => options,
+        };
+        let mut writer = XMLWriter::with_options(writer, options);
+        let (before, after) = self.prolog.split_at(self.doctype_position);
+        for node in before {
+            writer.write_node(node)?;
+        }
+        if let Some(doctype) = &self.doctype {
+            writer.doctype(doctype)?;
+        }
+        for node in after {
+            writer.write_node(node)?;
+        }
+        writer.write_element(&self.root)?;
+        for node in &self.epilog {
+            writer.write_node(node)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+impl From<XMLElement> for XMLDocument {
+    fn from(root: XMLElement) -> Self {
+        XMLDocument::new(root)
+    }
+}
+
+/// Parses a document from XML text, as by
+/// [from_str_with](XMLDocument::from_str_with) with the default options.
+impl FromStr for XMLDocument {
+    type Err = XMLError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_document(s, &ParseOptions::default())
+    }
+}
+
+/// Formats the document. The alternate flag (`{:#}`) writes compact output,
+/// as with [WriteOptions::compact].
+impl fmt::Display for XMLDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let options = if f.alternate() {
+            WriteOptions::compact()
+        } else {
+            WriteOptions::default()
+        };
+        let mut s: Vec<u8> = Vec::new();
+        self.write_with(&mut s, options).map_err(|_| fmt::Error)?;
+        write!(f, "{}", String::from_utf8(s).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// Checks that `node` may appear outside the root element.
+fn check_misc(node: &XMLNode) -> Result<(), XMLError> {
+    match node {
+        XMLNode::Comment(text) => check_comment(text),
+        XMLNode::ProcessingInstruction { target, data } => {
+            check_processing_instruction(target, data)
+        }
+        _ => Err(XMLError::ContentOutsideRoot),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {WriteOptions, XMLDeclaration, XMLDoctype, XMLDocument, XMLElement, XMLNode};
+
+    #[test]
+    fn write_document() {
+        let mut root = XMLElement::new("plist");
+        root.add_attribute("version", "1.0");
+        let mut doc = XMLDocument::new(root);
+        doc.set_declaration(Some(XMLDeclaration::new().standalone(Some(true))));
+        doc.set_doctype(Some(XMLDoctype::new("plist").public_id(
+            "-//Apple//DTD PLIST 1.0//EN",
+            "http://www.apple.com/DTDs/PropertyList-1.0.dtd",
+        )));
+        doc.add_prolog(XMLNode::Comment(" prolog ".to_owned()))
+            .unwrap();
+        doc.add_epilog(XMLNode::ProcessingInstruction {
+            target: "end".to_owned(),
+            data: String::new(),
+        })
+        .unwrap();
+        doc.root_mut().add_child(XMLElement::new("dict"));
+        assert!(doc.add_epilog(XMLNode::Text("text".to_owned())).is_err());
+        assert!(doc.add_prolog(XMLNode::Comment("--".to_owned())).is_err());
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<!-- prolog -->
+<plist version="1.0">
+	<dict />
+</plist>
+<?end?>
+"#;
+        assert_eq!(doc.to_string(), expected);
+    }
+
+    #[test]
+    fn doctype_position() {
+        let mut doc = XMLDocument::new(XMLElement::new("a"));
+        doc.set_declaration(None);
+        doc.add_prolog(XMLNode::Comment("c".to_owned())).unwrap();
+        doc.set_doctype(Some(XMLDoctype::new("a")));
+        doc.add_prolog(XMLNode::Comment("d".to_owned())).unwrap();
+        assert_eq!(format!("{:#}", doc), "<!--c--><!DOCTYPE a><!--d--><a/>");
+
+        let doc: XMLDocument = "<!--c--><!DOCTYPE a><a/>".parse().unwrap();
+        assert_eq!(format!("{:#}", doc), "<!--c--><!DOCTYPE a><a/>");
+    }
+
+    #[test]
+    fn element_write_is_shorthand() {
+        let mut root = XMLElement::new("root");
+        root.add_child(XMLElement::new("child"));
+        assert_eq!(XMLDocument::new(root.clone()).to_string(), root.to_string());
+
+        let write = |doc: &XMLDocument, options: WriteOptions| {
+            let mut output = Vec::new();
+            doc.write_with(&mut output, options).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let options = || WriteOptions::compact().declaration(None);
+        let mut doc = XMLDocument::new(root);
+        assert_eq!(write(&doc, options()), "<root><child/></root>");
+        doc.set_declaration(Some(XMLDeclaration::new()));
+        assert_eq!(
+            write(&doc, options()),
+            r#"<?xml version="1.0" encoding="UTF-8"?><root><child/></root>"#
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use parser::is_xml_char;
+use std::io::Write;
+use {InvalidChars, XMLError};
+
+/// Character encoding of XML output.
+///
+/// Characters in text and attribute values that the encoding cannot represent
+/// are written as numeric character references. Such characters in names and
+/// other markup cause [XMLError::Unencodable].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Encoding {
+    /// UTF-8, without a byte order mark.
+    Utf8,
+    /// Little-endian UTF-16, with a byte order mark.
+    Utf16Le,
+    /// Big-endian UTF-16, with a byte order mark.
+    Utf16Be,
+    /// ISO-8859-1 (Latin-1).
+    Iso8859_1,
+    /// Windows-1252.
+    Windows1252,
+}
+
+/// Unicode code points for the bytes `0x80` to `0x9F` in Windows-1252, or
+/// `None` for unassigned bytes. Other bytes match ISO-8859-1.
+const WINDOWS_1252_HIGH: [Option<char>; 32] = [
+    Some('\u{20AC}'),
+    None,
+    Some('\u{201A}'),
+    Some('\u{0192}'),
+    Some('\u{201E}'),
+    Some('\u{2026}'),
+    Some('\u{2020}'),
+    Some('\u{2021}'),
+    Some('\u{02C6}'),
+    Some('\u{2030}'),
+    Some('\u{0160}'),
+    Some('\u{2039}'),
+    Some('\u{0152}'),
+    None,
+    Some('\u{017D}'),
+    None,
+    None,
+    Some('\u{2018}'),
+    Some('\u{2019}'),
+    Some('\u{201C}'),
+    Some('\u{201D}'),
+    Some('\u{2022}'),
+    Some('\u{2013}'),
+    Some('\u{2014}'),
+    Some('\u{02DC}'),
+    Some('\u{2122}'),
+    Some('\u{0161}'),
+    Some('\u{203A}'),
+    Some('\u{0153}'),
+    None,
+    Some('\u{017E}'),
+    Some('\u{0178}'),
+];
+
+impl Encoding {
+    /// Returns the label used for this encoding in the XML declaration.
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le | Encoding::Utf16Be => "UTF-16",
+            Encoding::Iso8859_1 => "ISO-8859-1",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// Returns the encoding for a label from an XML declaration, if supported.
+    /// ASCII is treated as UTF-8.
+    pub(crate) fn for_label(label: &str) -> Option<Encoding> {
+        match label.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "us-ascii" | "ascii" => Some(Encoding::Utf8),
+            "utf-16" | "utf-16le" => Some(Encoding::Utf16Le),
+            "utf-16be" => Some(Encoding::Utf16Be),
+            "iso-8859-1" | "iso_8859-1" | "latin1" | "l1" => Some(Encoding::Iso8859_1),
+            "windows-1252" | "cp1252" => Some(Encoding::Windows1252),
+            _ => None,
+        }
+    }
+
+    /// Decodes `bytes`, which must not start with a byte order mark. Returns
+    /// `None` if the bytes are not valid in this encoding.
+    pub(crate) fn decode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if bytes.len() % 2 == 1 {
+                    return None;
+                }
+                let units = bytes.chunks(2).map(|pair| {
+                    if self == Encoding::Utf16Le {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    }
+                });
+                std::char::decode_utf16(units)
+                    .collect::<Result<_, _>>()
+                    .ok()
+            }
+            Encoding::Iso8859_1 => Some(bytes.iter().map(|&b| b as char).collect()),
+            Encoding::Windows1252 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[b as usize - 0x80],
+                    _ => Some(b as char),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns whether this encoding can represent `c`.
+    pub fn can_encode(self, c: char) -> bool {
+        self.encode_byte(c).is_some() || !self.is_single_byte()
+    }
+
+    fn is_single_byte(self) -> bool {
+        matches!(self, Encoding::Iso8859_1 | Encoding::Windows1252)
+    }
+
+    /// Encodes `c` for single byte encodings.
+    fn encode_byte(self, c: char) -> Option<u8> {
+        match (self, c as u32) {
+            (Encoding::Iso8859_1, code) if code <= 0xFF => Some(code as u8),
+            (Encoding::Windows1252, code) if code < 0x80 || (0xA0..=0xFF).contains(&code) => {
+                Some(code as u8)
+            }
+            (Encoding::Windows1252, _) => WINDOWS_1252_HIGH
+                .iter()
+                .position(|&mapped| mapped == Some(c))
+                .map(|i| 0x80 + i as u8),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of output being written, which determines how unrepresentable
+/// characters are handled.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Context {
+    Markup,
+    Content,
+    CData,
+}
+
+/// Transcodes UTF-8 output to an [Encoding].
+#[derive(Debug)]
+pub(crate) struct Encoder<W: Write> {
+    writer: W,
+    encoding: Encoding,
+    ascii_only: bool,
+    invalid_chars: InvalidChars,
+    started: bool,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates an encoder. If `ascii_only` is set, characters outside ASCII
+    /// are treated as unrepresentable. Characters not allowed in XML are
+    /// handled according to `invalid_chars` in character data, and are always
+    /// an error in markup.
+    pub(crate) fn new(
+        writer: W,
+        encoding: Encoding,
+        ascii_only: bool,
+        invalid_chars: InvalidChars,
+    ) -> Self {
+        Encoder {
+            writer,
+            encoding,
+            ascii_only,
+            invalid_chars,
+            started: false,
+        }
+    }
+
+    /// Writes markup, which must be representable in the encoding.
+    pub(crate) fn markup(&mut self, s: &str) -> Result<(), XMLError> {
+        self.write(s, Context::Markup)
+    }
+
+    /// Writes escaped character data, replacing unrepresentable characters with
+    /// character references.
+    pub(crate) fn content(&mut self, s: &str) -> Result<(), XMLError> {
+        self.write(s, Context::Content)
+    }
+
+    /// Writes the content of a CDATA section, ending the section around
+    /// unrepresentable characters to write them as character references.
+    pub(crate) fn cdata(&mut self, s: &str) -> Result<(), XMLError> {
+        self.write(s, Context::CData)
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), XMLError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write(&mut self, s: &str, context: Context) -> Result<(), XMLError> {
+        if !self.started {
+            self.started = true;
+            match self.encoding {
+                Encoding::Utf16Le => self.writer.write_all(&[0xFF, 0xFE])?,
+                Encoding::Utf16Be => self.writer.write_all(&[0xFE, 0xFF])?,
+                _ => {}
+            }
+        }
+        let mut bytes = Vec::with_capacity(s.len());
+        for c in s.chars() {
+            let c = match self.invalid_chars {
+                _ if is_xml_char(c) => c,
+                _ if context == Context::Markup => {
+                    return Err(XMLError::InvalidCharacter { character: c })
+                }
+                InvalidChars::Strip => continue,
+                InvalidChars::Replace(replacement) if is_xml_char(replacement) => replacement,
+                _ => return Err(XMLError::InvalidCharacter { character: c }),
+            };
+            if !self.encoding.can_encode(c) || (self.ascii_only && !c.is_ascii()) {
+                let reference = match context {
+                    Context::Markup => return Err(XMLError::Unencodable { character: c }),
+                    Context::Content => format!("&#x{:X};", c as u32),
+                    Context::CData => format!("]]>&#x{:X};<![CDATA[", c as u32),
+                };
+                bytes.extend_from_slice(reference.as_bytes());
+                continue;
+            }
+            match self.encoding {
+                Encoding::Utf8 => {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                Encoding::Utf16Le | Encoding::Utf16Be => {
+                    let mut buf = [0; 2];
+                    for unit in c.encode_utf16(&mut buf) {
+                        if self.encoding == Encoding::Utf16Le {
+                            bytes.extend_from_slice(&unit.to_le_bytes());
+                        } else {
+                            bytes.extend_from_slice(&unit.to_be_bytes());
+                        }
+                    }
+                }
+                _ => bytes.extend(self.encoding.encode_byte(c)),
+            }
+        }
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(encoding: Encoding, markup: &str, content: &str) -> Result<Vec<u8>, XMLError> {
+        let mut encoder = Encoder::new(Vec::new(), encoding, false, InvalidChars::Error);
+        encoder.markup(markup)?;
+        encoder.content(content)?;
+        Ok(encoder.into_inner())
+    }
+
+    #[test]
+    fn single_byte_encodings() {
+        assert_eq!(
+            encode(Encoding::Windows1252, "<é>", "€ \u{3B1}").unwrap(),
+            b"<\xE9>\x80 &#x3B1;"
+        );
+        assert_eq!(
+            encode(Encoding::Iso8859_1, "<a>", "€").unwrap(),
+            b"<a>&#x20AC;"
+        );
+        assert!(matches!(
+            encode(Encoding::Iso8859_1, "<\u{3B1}>", ""),
+            Err(XMLError::Unencodable {
+                character: '\u{3B1}'
+            })
+        ));
+    }
+
+    #[test]
+    fn utf16() {
+        assert_eq!(
+            encode(Encoding::Utf16Be, "<", "\u{1F600}").unwrap(),
+            b"\xFE\xFF\x00<\xD8\x3D\xDE\x00"
+        );
+        assert_eq!(
+            encode(Encoding::Utf16Le, "a", "").unwrap(),
+            b"\xFF\xFEa\x00"
+        );
+    }
+
+    #[test]
+    fn ascii_only() {
+        let mut encoder = Encoder::new(Vec::new(), Encoding::Utf8, true, InvalidChars::Error);
+        encoder.markup("<a>").unwrap();
+        encoder.content("caf\u{E9} \u{1F600}").unwrap();
+        assert!(encoder.markup("\u{E9}").is_err());
+        encoder.cdata("\u{E9}!").unwrap();
+        assert_eq!(
+            encoder.into_inner(),
+            &b"<a>caf&#xE9; &#x1F600;]]>&#xE9;<![CDATA[!"[..]
+        );
+    }
+
+    #[test]
+    fn invalid_chars() {
+        let write = |invalid_chars| {
+            let mut encoder = Encoder::new(Vec::new(), Encoding::Utf8, false, invalid_chars);
+            encoder.content("a\u{0}b\u{FFFE}\t")?;
+            Ok::<_, XMLError>(encoder.into_inner())
+        };
+        assert_eq!(write(InvalidChars::Strip).unwrap(), b"ab\t");
+        assert_eq!(write(InvalidChars::Replace('?')).unwrap(), b"a?b?\t");
+        assert!(matches!(
+            write(InvalidChars::Error),
+            Err(XMLError::InvalidCharacter { character: '\u{0}' })
+        ));
+        assert!(write(InvalidChars::Replace('\u{1}')).is_err());
+
+        let mut encoder = Encoder::new(Vec::new(), Encoding::Utf8, false, InvalidChars::Strip);
+        assert!(matches!(
+            encoder.markup("a\u{1}"),
+            Err(XMLError::InvalidCharacter { character: '\u{1}' })
+        ));
+    }
+}
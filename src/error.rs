@@ -0,0 +1,171 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors produced while building or writing XML documents.
+#[derive(Debug)]
+pub enum XMLError {
+    /// Content was added to an element that cannot hold it, such as a child
+    /// element added to an element with text. Carries the element's name.
+    ContentConflict {
+        /// Name of the element the content was added to.
+        element: String,
+    },
+    /// A child element position is past the end of an element's children.
+    IndexOutOfBounds {
+        /// The requested position.
+        index: usize,
+        /// Number of child elements.
+        len: usize,
+    },
+    /// A name is not a valid XML element or attribute name.
+    InvalidName {
+        /// The offending name.
+        name: String,
+    },
+    /// Comment text contains `--`, ends with `-` or contains characters not
+    /// allowed in XML.
+    InvalidComment {
+        /// The offending comment text.
+        comment: String,
+    },
+    /// A processing instruction has an invalid or reserved target, or its data
+    /// contains `?>` or characters not allowed in XML.
+    InvalidProcessingInstruction {
+        /// Target of the processing instruction.
+        target: String,
+    },
+    /// A document type declaration has an invalid name, identifier or
+    /// internal subset.
+    InvalidDoctype {
+        /// Name in the document type declaration.
+        name: String,
+    },
+    /// A document type declaration was written after the root element was
+    /// started, or more than once.
+    MisplacedDoctype,
+    /// An end tag did not match the currently open element.
+    MismatchedEndTag {
+        /// Name of the open element, if any.
+        expected: Option<String>,
+        /// Name given for the end tag.
+        found: String,
+    },
+    /// An attribute was written after the content of an element was started.
+    AttributeAfterContent {
+        /// Name of the attribute.
+        attribute: String,
+    },
+    /// Content was written before or after the root element.
+    ContentOutsideRoot,
+    /// A document was finished without a root element or with elements still
+    /// open.
+    IncompleteDocument,
+    /// A character that XML does not allow was written in markup, or in
+    /// character data with [InvalidChars::Error](::InvalidChars::Error)
+    /// selected.
+    InvalidCharacter {
+        /// The invalid character.
+        character: char,
+    },
+    /// A character in markup, such as a name, cannot be represented in the
+    /// output encoding.
+    Unencodable {
+        /// The unrepresentable character.
+        character: char,
+    },
+    /// XML text could not be parsed.
+    Parse {
+        /// Line of the error, starting from 1.
+        line: usize,
+        /// Column of the error in characters, starting from 1.
+        column: usize,
+        /// Description of the error.
+        message: String,
+    },
+    /// An error from the underlying reader or writer.
+    Io(io::Error),
+}
+
+impl fmt::Display for XMLError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XMLError::ContentConflict { element } => {
+                write!(f, "conflicting content added to element `{}`", element)
+            }
+            XMLError::IndexOutOfBounds { index, len } => write!(
+                f,
+                "child index {} out of bounds for element with {} children",
+                index, len
+            ),
+            XMLError::InvalidName { name } => write!(f, "invalid XML name `{}`", name),
+            XMLError::InvalidComment { comment } => write!(f, "invalid comment `{}`", comment),
+            XMLError::InvalidProcessingInstruction { target } => {
+                write!(f, "invalid processing instruction `{}`", target)
+            }
+            XMLError::InvalidDoctype { name } => {
+                write!(f, "invalid document type declaration for `{}`", name)
+            }
+            XMLError::MisplacedDoctype => {
+                write!(f, "document type declaration must precede the root element")
+            }
+            XMLError::MismatchedEndTag {
+                expected: Some(expected),
+                found,
+            } => write!(f, "end tag `{}` does not match `{}`", found, expected),
+            XMLError::MismatchedEndTag {
+                expected: None,
+                found,
+            } => write!(f, "end tag `{}` without an open element", found),
+            XMLError::AttributeAfterContent { attribute } => {
+                write!(f, "attribute `{}` written outside a start tag", attribute)
+            }
+            XMLError::ContentOutsideRoot => write!(f, "content written outside the root element"),
+            XMLError::IncompleteDocument => {
+                write!(f, "document has unclosed or missing root element")
+            }
+            XMLError::InvalidCharacter { character } => {
+                write!(f, "character {:?} is not allowed in XML", character)
+            }
+            XMLError::Unencodable { character } => write!(
+                f,
+                "character {:?} cannot be represented in the output encoding",
+                character
+            ),
+            XMLError::Parse {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "parse error at line {}, column {}: {}",
+                line, column, message
+            ),
+            XMLError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl Error for XMLError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            XMLError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for XMLError {
+    fn from(err: io::Error) -> Self {
+        XMLError::Io(err)
+    }
+}
+
+impl From<XMLError> for io::Error {
+    fn from(err: XMLError) -> Self {
+        match err {
+            XMLError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidInput, err),
+        }
+    }
+}
@@ -1,14 +1,21 @@
 //! `simple_xml_builder` provides basic functionality for building and
 //! outputting XML documents.
 //!
-//! The constructed model is write-only.
+//! Existing documents can also be parsed into the same model, modified, and
+//! written back.
 //!
 //! # Usage
 //!
 //! Use [XMLElement] to create elements with tags,
 //! attributes, and either text or children.
 //! You can write an XML document by calling
-//! [write](XMLElement::write) on your root element.
+//! [write](XMLElement::write) on your root element, or use [XMLDocument] to
+//! add a document type declaration, comments or processing instructions
+//! around the root element.
+//! XML text can be parsed with [str::parse], or with
+//! [XMLElement::from_reader] and [XMLDocument::from_reader].
+//! Large documents can be written without building a tree using
+//! [XMLWriter], and read as a stream of events using [XMLReader].
 //!
 //! # Example
 //!
@@ -41,182 +48,867 @@
 //! ```
 //! `sample.xml` will contain:
 //! ```xml
-//! <?xml version = "1.0" encoding = "UTF-8"?>
+//! <?xml version="1.0" encoding="UTF-8"?>
 //! <person id="232">
 //!     <name>Joe Schmoe</name>
 //!     <age>24</age>
 //!     <hobbies />
 //! </person>
 //! ```
+//!
+//! The same element can be built in a single expression with the chainable
+//! methods [attr](XMLElement::attr), [child](XMLElement::child),
+//! [with_child](XMLElement::with_child) and [with_text](XMLElement::with_text):
+//!
+//! ```rust
+//! use simple_xml_builder::XMLElement;
+//!
+//! let person = XMLElement::new("person")
+//!     .attr("id", 232)
+//!     .with_child("name", |name| name.with_text("Joe Schmoe"))
+//!     .with_child("age", |age| age.with_text(24))
+//!     .child(XMLElement::new("hobbies"));
+//! ```
 
 #![doc(html_root_url = "https://docs.rs/simple-xml-builder/1.1.0")]
 
 extern crate indexmap;
+
+mod doctype;
+mod document;
+mod encoding;
+mod error;
+mod name;
+mod namespace;
+mod options;
+mod parser;
+mod reader;
+mod writer;
+
+pub use doctype::XMLDoctype;
+pub use document::XMLDocument;
+pub use encoding::Encoding;
+pub use error::XMLError;
 use indexmap::IndexMap;
+use name::QName;
+pub use name::{is_valid_name, is_valid_ncname};
+pub use options::{
+    AttributeQuote, EmptyElement, Escaping, Indent, InvalidChars, LineEnding, WriteOptions,
+    XMLDeclaration,
+};
+pub use parser::ParseOptions;
+pub use reader::{XMLEvent, XMLReader};
+use std::borrow::Cow;
 use std::fmt;
-use std::io::{self, Write};
+use std::io::{Read, Write};
+use std::str::FromStr;
+pub use writer::XMLWriter;
 
 /// Represents an XML element
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct XMLElement {
-    name: String,
-    attributes: IndexMap<String, String>,
-    content: XMLElementContent,
+    name: QName,
+    attributes: IndexMap<QName, String>,
+    namespace_prefixes: IndexMap<String, String>,
+    content: Vec<XMLNode>,
 }
 
+/// A node in the content of an [XMLElement].
+///
+/// An element's content is an ordered list of nodes, which allows text and
+/// child elements to be interleaved (mixed content).
 #[derive(Debug, Clone, Eq, PartialEq)]
-enum XMLElementContent {
-    Empty,
-    Elements(Vec<XMLElement>),
+pub enum XMLNode {
+    /// A child element.
+    Element(XMLElement),
+    /// A run of text. The text is escaped when written.
     Text(String),
+    /// A CDATA section. Occurrences of `]]>` are split across sections when
+    /// written.
+    CData(String),
+    /// A comment. When added to an element, the text is sanitized as by
+    /// [add_comment](XMLElement::add_comment).
+    Comment(String),
+    /// A processing instruction, such as `<?target data?>`.
+    ProcessingInstruction {
+        /// The target application name.
+        target: String,
+        /// The instruction data, written as is. Left out if empty.
+        data: String,
+    },
+    /// Markup written verbatim, without escaping or checks. See
+    /// [add_raw_unchecked](XMLElement::add_raw_unchecked).
+    Raw(String),
+}
+
+impl From<XMLElement> for XMLNode {
+    fn from(element: XMLElement) -> Self {
+        XMLNode::Element(element)
+    }
 }
 
+/// Formats the element as an XML document. The alternate flag (`{:#}`) writes
+/// compact output, as with [WriteOptions::compact].
 impl fmt::Display for XMLElement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let options = if f.alternate() {
+            WriteOptions::compact()
+        } else {
+            WriteOptions::default()
+        };
         let mut s: Vec<u8> = Vec::new();
-        self.write(&mut s)
-            .expect("Failure writing output to Vec<u8>");
-        write!(f, "{}", unsafe { String::from_utf8_unchecked(s) })
+        self.write_with(&mut s, options).map_err(|_| fmt::Error)?;
+        write!(f, "{}", String::from_utf8(s).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// Parses an XML document, returning its root element. See
+/// [XMLDocument::from_str_with] for details.
+impl FromStr for XMLElement {
+    type Err = XMLError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse::<XMLDocument>()?.into_root())
     }
 }
 
 impl XMLElement {
     /// Creates a new empty XML element using the given name for the tag.
+    ///
+    /// The name is not checked, except that characters not allowed in XML are
+    /// replaced with U+FFFD REPLACEMENT CHARACTER.
     pub fn new(name: impl ToString) -> Self {
         XMLElement {
-            name: name.to_string(),
+            name: QName::new(name.to_string()),
             attributes: IndexMap::new(),
-            content: XMLElementContent::Empty,
+            namespace_prefixes: IndexMap::new(),
+            content: Vec::new(),
         }
     }
 
+    /// Creates a new empty XML element in the given namespace, using the given
+    /// local name for the tag.
+    ///
+    /// A prefix for the namespace is chosen when writing. Use
+    /// [set_namespace_prefix](XMLElement::set_namespace_prefix) to pick one.
+    pub fn new_ns(namespace: impl ToString, name: impl ToString) -> Self {
+        let mut elem = XMLElement::new("");
+        elem.name = QName::new_ns(namespace.to_string(), name.to_string());
+        elem
+    }
+
+    /// Parses an XML document from a reader, returning its root element. See
+    /// [XMLDocument::from_reader] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::Parse] if the input is not a well-formed XML
+    /// document, or [XMLError::Io] for errors from reading.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, XMLError> {
+        Ok(XMLDocument::from_reader(reader)?.into_root())
+    }
+
+    /// Creates a new empty XML element, checking that the name is a valid
+    /// element name.
+    ///
+    /// The name must match the XML `Name` production, contain at most one
+    /// colon separating two `NCName`s, and not use the `xmlns` prefix, which
+    /// is reserved for namespace declarations.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidName] if the name is not valid.
+    pub fn try_new(name: impl ToString) -> Result<Self, XMLError> {
+        let name = name.to_string();
+        name::check_element_name(&name)?;
+        Ok(XMLElement::new(name))
+    }
+
+    /// Creates a new empty XML element in the given namespace, checking that
+    /// the local name is a valid `NCName`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidName] if the name is not valid.
+    pub fn try_new_ns(namespace: impl ToString, name: impl ToString) -> Result<Self, XMLError> {
+        let name = name.to_string();
+        name::check_local_name(&name)?;
+        Ok(XMLElement::new_ns(namespace, name))
+    }
+
+    /// Adds an attribute and returns the element, like
+    /// [add_attribute](XMLElement::add_attribute).
+    pub fn attr(mut self, name: impl ToString, value: impl ToString) -> Self {
+        self.add_attribute(name, value);
+        self
+    }
+
+    /// Adds a child element and returns the element, like
+    /// [add_child](XMLElement::add_child).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element contains text.
+    pub fn child(mut self, child: XMLElement) -> Self {
+        self.add_child(child);
+        self
+    }
+
+    /// Adds a child element with the given name, built by `build`, and
+    /// returns the element.
+    ///
+    /// ```rust
+    /// use simple_xml_builder::XMLElement;
+    ///
+    /// let list = XMLElement::new("ul")
+    ///     .with_child("li", |item| item.attr("class", "first").with_text("one"))
+    ///     .with_child("li", |item| item.with_text("two"));
+    /// assert_eq!(list.children().count(), 2);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element contains text.
+    pub fn with_child(
+        self,
+        name: impl ToString,
+        build: impl FnOnce(XMLElement) -> XMLElement,
+    ) -> Self {
+        self.child(build(XMLElement::new(name)))
+    }
+
+    /// Adds text and returns the element, like
+    /// [add_text](XMLElement::add_text).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element is not empty.
+    pub fn with_text(mut self, text: impl ToString) -> Self {
+        self.add_text(text);
+        self
+    }
+
+    /// Returns the name of the element, without a namespace prefix if it was
+    /// created in a namespace.
+    pub fn name(&self) -> &str {
+        &self.name.local
+    }
+
+    /// Returns the namespace of the element, if it was created in one.
+    pub fn namespace(&self) -> Option<&str> {
+        self.name.namespace.as_deref()
+    }
+
+    /// Returns an iterator over the names and values of the element's
+    /// attributes, in the order they were added. Names of attributes in a
+    /// namespace are returned without a prefix; use
+    /// [attributes_ns](XMLElement::attributes_ns) to tell them apart.
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attributes
+            .iter()
+            .map(|(name, value)| (name.local.as_str(), value.as_str()))
+    }
+
+    /// Returns an iterator over the namespaces, names and values of the
+    /// element's attributes, in the order they were added.
+    pub fn attributes_ns(&self) -> impl Iterator<Item = (Option<&str>, &str, &str)> {
+        self.attributes.iter().map(|(name, value)| {
+            (
+                name.namespace.as_deref(),
+                name.local.as_str(),
+                value.as_str(),
+            )
+        })
+    }
+
+    /// Returns the value of the attribute with the given name and no
+    /// namespace, if present.
+    ///
+    /// ```rust
+    /// use simple_xml_builder::XMLElement;
+    ///
+    /// let mut link = XMLElement::new("a");
+    /// link.add_attribute("href", "?a=1&b=2");
+    /// assert_eq!(link.attribute("href"), Some("?a=1&b=2"));
+    /// assert!(link.attribute("title").is_none());
+    /// ```
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .get(&QName::new(name.to_owned()))
+            .map(String::as_str)
+    }
+
+    /// Returns the value of the attribute with the given namespace and local
+    /// name, if present.
+    pub fn attribute_ns(&self, namespace: &str, name: &str) -> Option<&str> {
+        self.attributes
+            .get(&QName::new_ns(namespace.to_owned(), name.to_owned()))
+            .map(String::as_str)
+    }
+
+    /// Returns an iterator over the child elements, skipping text and other
+    /// nodes.
+    pub fn children(&self) -> impl Iterator<Item = &XMLElement> {
+        self.content.iter().filter_map(|node| match node {
+            XMLNode::Element(child) => Some(child),
+            _ => None,
+        })
+    }
+
+    /// Returns the text directly within the element, joining text and CDATA
+    /// nodes. Text within child elements is not included.
+    pub fn text(&self) -> Cow<'_, str> {
+        let mut parts = self.content.iter().filter_map(|node| match node {
+            XMLNode::Text(text) | XMLNode::CData(text) => Some(Cow::Borrowed(text.as_str())),
+            _ => None,
+        });
+        let first = parts.next().unwrap_or(Cow::Borrowed(""));
+        parts.fold(first, |text, part| Cow::Owned(text.into_owned() + &part))
+    }
+
+    /// Returns whether the element has no content. Attributes are not
+    /// considered content.
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
     /// Adds an attribute to the XML element. The attribute value can take any
     /// type which implements [`fmt::Display`].
     pub fn add_attribute(&mut self, name: impl ToString, value: impl ToString) {
         self.attributes
-            .insert(name.to_string(), escape_str(&value.to_string()));
+            .insert(QName::new(name.to_string()), value.to_string());
+    }
+
+    /// Adds an attribute in the given namespace to the XML element.
+    pub fn add_attribute_ns(
+        &mut self,
+        namespace: impl ToString,
+        name: impl ToString,
+        value: impl ToString,
+    ) {
+        self.attributes.insert(
+            QName::new_ns(namespace.to_string(), name.to_string()),
+            value.to_string(),
+        );
+    }
+
+    /// Adds an attribute to the XML element, checking that the name is a valid
+    /// attribute name.
+    ///
+    /// The same rules as [try_new](XMLElement::try_new) apply, except that
+    /// the `xmlns` prefix is allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidName] if the name is not valid.
+    pub fn try_add_attribute(
+        &mut self,
+        name: impl ToString,
+        value: impl ToString,
+    ) -> Result<(), XMLError> {
+        let name = name.to_string();
+        name::check_attribute_name(&name)?;
+        self.add_attribute(name, value);
+        Ok(())
+    }
+
+    /// Adds an attribute in the given namespace to the XML element, checking
+    /// that the local name is a valid `NCName`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidName] if the name is not valid.
+    pub fn try_add_attribute_ns(
+        &mut self,
+        namespace: impl ToString,
+        name: impl ToString,
+        value: impl ToString,
+    ) -> Result<(), XMLError> {
+        let name = name.to_string();
+        name::check_local_name(&name)?;
+        self.add_attribute_ns(namespace, name, value);
+        Ok(())
+    }
+
+    /// Sets an attribute, returning the previous value if the attribute was
+    /// already present. An existing attribute keeps its
+    /// position.
+    pub fn set_attribute(&mut self, name: impl ToString, value: impl ToString) -> Option<String> {
+        self.attributes
+            .insert(QName::new(name.to_string()), value.to_string())
+    }
+
+    /// Removes the attribute with the given name and no namespace, returning
+    /// its value if it was present.
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        self.attributes.shift_remove(&QName::new(name.to_owned()))
+    }
+
+    /// Sets the preferred prefix for a namespace. An empty prefix makes the
+    /// namespace the default namespace.
+    ///
+    /// Namespaces are declared once, on the root element being written. The
+    /// first preference found in the tree for a namespace is used, unless its
+    /// prefix is not a valid `NCName` or is already taken, including by names
+    /// written with a literal prefix, in which case a prefix is generated. The
+    /// default namespace is likewise taken by a literal `xmlns` attribute.
+    /// Namespaces given a non-empty prefix are declared even if no name uses
+    /// them, so they can be referred to in attribute values and text.
+    ///
+    /// ```rust
+    /// use simple_xml_builder::XMLElement;
+    ///
+    /// let mut feed = XMLElement::new_ns("http://www.w3.org/2005/Atom", "feed");
+    /// feed.set_namespace_prefix("http://www.w3.org/2005/Atom", "");
+    /// let mut link = XMLElement::new_ns("http://www.w3.org/2005/Atom", "link");
+    /// link.add_attribute_ns("http://www.w3.org/1999/xlink", "href", "a.xml");
+    /// link.set_namespace_prefix("http://www.w3.org/1999/xlink", "xlink");
+    /// feed.add_child(link);
+    /// ```
+    /// writes the elements as:
+    /// ```xml
+    /// <feed xmlns="http://www.w3.org/2005/Atom" xmlns:xlink="http://www.w3.org/1999/xlink">
+    ///     <link xlink:href="a.xml" />
+    /// </feed>
+    /// ```
+    pub fn set_namespace_prefix(&mut self, namespace: impl ToString, prefix: impl ToString) {
+        self.namespace_prefixes
+            .insert(namespace.to_string(), prefix.to_string());
     }
 
     /// Adds a child element to the XML element.
     /// The new child will be placed after previously added children.
     ///
     /// This method may only be called on an element that has children or is
-    /// empty.
+    /// empty. Use [add_node](XMLElement::add_node) to build mixed content.
     ///
     /// # Panics
     ///
-    /// Panics if the element contains text.
+    /// Panics if the element contains text other than whitespace. See
+    /// [try_add_child](XMLElement::try_add_child) for a non-panicking version.
     pub fn add_child(&mut self, child: XMLElement) {
-        use XMLElementContent::*;
-        match self.content {
-            Empty => {
-                self.content = Elements(vec![child]);
-            }
-            Elements(ref mut list) => {
-                list.push(child);
-            }
-            Text(_) => {
-                panic!("Attempted adding child element to element with text.");
-            }
+        if self.try_add_child(child).is_err() {
+            panic!("Attempted adding child element to element with text.");
         }
     }
 
+    /// Adds a child element to the XML element, like
+    /// [add_child](XMLElement::add_child).
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::ContentConflict] if the element contains text other
+    /// than whitespace.
+    pub fn try_add_child(&mut self, child: XMLElement) -> Result<(), XMLError> {
+        if self.has_text() {
+            return Err(self.conflict());
+        }
+        self.content.push(XMLNode::Element(child));
+        Ok(())
+    }
+
+    /// Inserts a child element at position `index` among the child elements,
+    /// after any other nodes preceding the child currently at that position.
+    /// Unlike [add_child](XMLElement::add_child), this may be used on elements
+    /// with mixed content.
+    ///
+    /// ```rust
+    /// use simple_xml_builder::XMLElement;
+    ///
+    /// let mut p: XMLElement = "<p>a<b/>c</p>".parse().unwrap();
+    /// p.insert_child(0, XMLElement::new("i")).unwrap();
+    /// assert_eq!(p.to_compact_string(), r#"<?xml version="1.0" encoding="UTF-8"?><p>a<i/><b/>c</p>"#);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::IndexOutOfBounds] if `index` is greater than the
+    /// number of child elements.
+    pub fn insert_child(&mut self, index: usize, child: XMLElement) -> Result<(), XMLError> {
+        let position = match self.child_position(index) {
+            Some(position) => position,
+            None if index == self.children().count() => self.content.len(),
+            None => return Err(self.out_of_bounds(index)),
+        };
+        self.content.insert(position, XMLNode::Element(child));
+        Ok(())
+    }
+
+    /// Removes and returns the child element at position `index` among the
+    /// child elements.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::IndexOutOfBounds] if there is no child element at
+    /// `index`.
+    pub fn remove_child(&mut self, index: usize) -> Result<XMLElement, XMLError> {
+        match self.child_position(index) {
+            Some(position) => match self.content.remove(position) {
+                XMLNode::Element(child) => Ok(child),
+                _ => unreachable!("child_position returns element positions"),
+            },
+            None => Err(self.out_of_bounds(index)),
+        }
+    }
+
+    /// Keeps only the child elements for which `keep` returns `true`. Other
+    /// nodes are not affected.
+    ///
+    /// ```rust
+    /// use simple_xml_builder::XMLElement;
+    ///
+    /// let mut list: XMLElement = "<ul><li>a</li><li/><li>b</li></ul>".parse().unwrap();
+    /// list.retain_children(|item| !item.is_empty());
+    /// assert_eq!(list.children().count(), 2);
+    /// ```
+    pub fn retain_children(&mut self, mut keep: impl FnMut(&XMLElement) -> bool) {
+        self.content.retain(|node| match node {
+            XMLNode::Element(child) => keep(child),
+            _ => true,
+        });
+    }
+
+    /// Returns an iterator over mutable references to the child elements.
+    pub fn children_mut(&mut self) -> impl Iterator<Item = &mut XMLElement> {
+        self.content.iter_mut().filter_map(|node| match node {
+            XMLNode::Element(child) => Some(child),
+            _ => None,
+        })
+    }
+
+    /// Removes all content from the element, leaving its attributes.
+    pub fn clear_content(&mut self) {
+        self.content.clear();
+    }
+
+    /// Replaces all content of the element with text.
+    pub fn replace_text(&mut self, text: impl ToString) {
+        self.content.clear();
+        self.content.push(XMLNode::Text(text.to_string()));
+    }
+
     /// Adds text to the XML element.
     ///
     /// This method may only be called on an empty element.
+    /// Use [add_node](XMLElement::add_node) to build mixed content.
     ///
     /// # Panics
     ///
-    /// Panics if the element is not empty.
+    /// Panics if the element is not empty. See
+    /// [try_add_text](XMLElement::try_add_text) for a non-panicking version.
     pub fn add_text(&mut self, text: impl ToString) {
-        use XMLElementContent::*;
-        match self.content {
-            Empty => {
-                self.content = Text(escape_str(&text.to_string()));
-            }
-            _ => {
-                panic!("Attempted adding text to non-empty element.");
-            }
+        if self.try_add_text(text).is_err() {
+            panic!("Attempted adding text to non-empty element.");
         }
     }
 
-    /// Outputs a UTF-8 XML document, where this element is the root element.
+    /// Adds text to the XML element, like [add_text](XMLElement::add_text).
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::ContentConflict] if the element is not empty.
+    pub fn try_add_text(&mut self, text: impl ToString) -> Result<(), XMLError> {
+        if !self.content.is_empty() {
+            return Err(self.conflict());
+        }
+        self.content.push(XMLNode::Text(text.to_string()));
+        Ok(())
+    }
+
+    /// Appends a node to the content of the XML element.
+    ///
+    /// Unlike [add_child](XMLElement::add_child) and
+    /// [add_text](XMLElement::add_text), this allows text and child elements
+    /// to be freely interleaved.
+    ///
+    /// ```rust
+    /// use simple_xml_builder::{XMLElement, XMLNode};
     ///
-    /// Output is properly indented.
+    /// let mut p = XMLElement::new("p");
+    /// p.add_node(XMLNode::Text("Hello ".to_owned()));
+    /// let mut b = XMLElement::new("b");
+    /// b.add_text("world");
+    /// p.add_node(b);
+    /// p.add_node(XMLNode::Text("!".to_owned()));
+    /// ```
+    ///
+    /// Comments are sanitized as by [add_comment](XMLElement::add_comment),
+    /// and markup is added as by
+    /// [add_raw_unchecked](XMLElement::add_raw_unchecked).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is an invalid processing instruction, as for
+    /// [add_processing_instruction](XMLElement::add_processing_instruction).
+    /// See [try_add_node](XMLElement::try_add_node) for a non-panicking
+    /// version.
+    pub fn add_node(&mut self, node: impl Into<XMLNode>) {
+        let node = match node.into() {
+            XMLNode::Comment(text) => XMLNode::Comment(sanitize_comment(text)),
+            XMLNode::Raw(xml) => return self.add_raw_unchecked(xml),
+            node => node,
+        };
+        if let Err(err) = self.try_add_node(node) {
+            panic!("Attempted adding {}.", err);
+        }
+    }
+
+    /// Appends a node to the content of the XML element, like
+    /// [add_node](XMLElement::add_node), but checking comments and markup
+    /// instead of sanitizing them.
     ///
     /// # Errors
     ///
-    /// Returns Errors from writing to the Write object.
-    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        writeln!(writer, r#"<?xml version = "1.0" encoding = "UTF-8"?>"#)?;
-        self.write_level(&mut writer, 0)
-    }
-
-    fn write_level<W: Write>(&self, writer: &mut W, level: usize) -> io::Result<()> {
-        use XMLElementContent::*;
-        let prefix = "\t".repeat(level);
-        match &self.content {
-            Empty => {
-                writeln!(
-                    writer,
-                    "{}<{}{} />",
-                    prefix,
-                    self.name,
-                    self.attribute_string()
-                )?;
-            }
-            Elements(list) => {
-                writeln!(
-                    writer,
-                    "{}<{}{}>",
-                    prefix,
-                    self.name,
-                    self.attribute_string()
-                )?;
-                for elem in list {
-                    elem.write_level(writer, level + 1)?;
-                }
-                writeln!(writer, "{}</{}>", prefix, self.name)?;
-            }
-            Text(text) => {
-                writeln!(
-                    writer,
-                    "{}<{}{}>{}</{1}>",
-                    prefix,
-                    self.name,
-                    self.attribute_string(),
-                    text
-                )?;
+    /// Returns [XMLError::InvalidComment],
+    /// [XMLError::InvalidProcessingInstruction] or [XMLError::Parse] for nodes
+    /// that [try_add_comment](XMLElement::try_add_comment),
+    /// [try_add_processing_instruction](XMLElement::try_add_processing_instruction)
+    /// or [try_add_raw](XMLElement::try_add_raw) would reject.
+    pub fn try_add_node(&mut self, node: impl Into<XMLNode>) -> Result<(), XMLError> {
+        let node = node.into();
+        match &node {
+            XMLNode::Comment(text) => check_comment(text)?,
+            XMLNode::ProcessingInstruction { target, data } => {
+                check_processing_instruction(target, data)?
             }
+            XMLNode::Raw(xml) => parser::check_fragment(xml)?,
+            _ => {}
         }
+        self.content.push(node);
         Ok(())
     }
 
-    fn attribute_string(&self) -> String {
-        if self.attributes.is_empty() {
-            "".to_owned()
-        } else {
-            let mut result = "".to_owned();
-            for (k, v) in &self.attributes {
-                result = result + &format!(r#" {}="{}""#, k, v);
-            }
-            result
+    /// Appends a CDATA section to the content of the XML element. Like text,
+    /// CDATA sections may be interleaved with child elements.
+    ///
+    /// The content is written without escaping. If it contains `]]>`, it is
+    /// split into multiple sections so the output stays well-formed.
+    pub fn add_cdata(&mut self, text: impl ToString) {
+        self.content.push(XMLNode::CData(text.to_string()));
+    }
+
+    /// Appends a comment to the content of the XML element. Comments may be
+    /// interleaved with any other content.
+    ///
+    /// The text is written as is, except that `--` is not allowed in comments,
+    /// so any occurrence has a space inserted (`- -`), and a space is appended
+    /// if the text ends with `-`. Characters not allowed in XML are replaced
+    /// with U+FFFD REPLACEMENT CHARACTER. See
+    /// [try_add_comment](XMLElement::try_add_comment) to reject such text
+    /// instead.
+    pub fn add_comment(&mut self, text: impl ToString) {
+        self.content
+            .push(XMLNode::Comment(sanitize_comment(text.to_string())));
+    }
+
+    /// Appends a comment to the content of the XML element, like
+    /// [add_comment](XMLElement::add_comment).
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidComment] if the text contains `--`, ends with
+    /// `-` or contains characters not allowed in XML.
+    pub fn try_add_comment(&mut self, text: impl ToString) -> Result<(), XMLError> {
+        let text = text.to_string();
+        check_comment(&text)?;
+        self.content.push(XMLNode::Comment(text));
+        Ok(())
+    }
+
+    /// Appends a processing instruction to the content of the XML element.
+    /// Processing instructions may be interleaved with any other content.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the target is not a valid name, is `xml` in any case, or the
+    /// data contains `?>` or characters not allowed in XML. See
+    /// [try_add_processing_instruction](XMLElement::try_add_processing_instruction)
+    /// for a non-panicking version.
+    pub fn add_processing_instruction(&mut self, target: impl ToString, data: impl ToString) {
+        if let Err(err) = self.try_add_processing_instruction(target, data) {
+            panic!("Attempted adding {}.", err);
+        }
+    }
+
+    /// Appends a processing instruction to the content of the XML element,
+    /// like [add_processing_instruction](XMLElement::add_processing_instruction).
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidProcessingInstruction] if the target is not a
+    /// valid name, is `xml` in any case, or the data contains `?>` or
+    /// characters not allowed in XML.
+    pub fn try_add_processing_instruction(
+        &mut self,
+        target: impl ToString,
+        data: impl ToString,
+    ) -> Result<(), XMLError> {
+        let target = target.to_string();
+        let data = data.to_string();
+        check_processing_instruction(&target, &data)?;
+        self.content
+            .push(XMLNode::ProcessingInstruction { target, data });
+        Ok(())
+    }
+
+    /// Appends markup to the content of the XML element, written exactly as
+    /// given. Like text, raw markup may be interleaved with child elements,
+    /// and the element is written on a single line.
+    ///
+    /// The markup is not checked, so malformed markup produces a malformed
+    /// document. Only characters not allowed in XML are replaced with U+FFFD
+    /// REPLACEMENT CHARACTER. See [try_add_raw](XMLElement::try_add_raw) for a
+    /// checked version.
+    pub fn add_raw_unchecked(&mut self, xml: impl ToString) {
+        self.content
+            .push(XMLNode::Raw(sanitize_chars(xml.to_string())));
+    }
+
+    /// Appends markup to the content of the XML element, like
+    /// [add_raw_unchecked](XMLElement::add_raw_unchecked), after checking
+    /// that it is well-formed element content. Namespace prefixes are not
+    /// checked, since they may be declared by the surrounding elements.
+    ///
+    /// ```rust
+    /// use simple_xml_builder::XMLElement;
+    ///
+    /// let mut body = XMLElement::new("body");
+    /// body.try_add_raw("<p>Hello <b>world</b></p>").unwrap();
+    /// assert!(body.try_add_raw("<p>Unclosed").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::Parse] if the markup is not well-formed.
+    pub fn try_add_raw(&mut self, xml: impl ToString) -> Result<(), XMLError> {
+        let xml = xml.to_string();
+        parser::check_fragment(&xml)?;
+        self.content.push(XMLNode::Raw(xml));
+        Ok(())
+    }
+
+    fn conflict(&self) -> XMLError {
+        XMLError::ContentConflict {
+            element: self.name.local.clone(),
+        }
+    }
+
+    fn out_of_bounds(&self, index: usize) -> XMLError {
+        XMLError::IndexOutOfBounds {
+            index,
+            len: self.children().count(),
         }
     }
+
+    /// Returns the position in the content of the child element at `index`
+    /// among the child elements.
+    fn child_position(&self, index: usize) -> Option<usize> {
+        self.content
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, XMLNode::Element(_)))
+            .nth(index)
+            .map(|(position, _)| position)
+    }
+
+    /// Returns whether the element contains text other than whitespace, such
+    /// as indentation kept from parsing, or CDATA.
+    fn has_text(&self) -> bool {
+        self.content.iter().any(|node| match node {
+            XMLNode::Text(text) => !text.chars().all(parser::is_whitespace),
+            XMLNode::CData(_) => true,
+            _ => false,
+        })
+    }
+
+    /// Outputs a UTF-8 XML document, where this element is the root element.
+    /// This is shorthand for writing an [XMLDocument] with this root element.
+    ///
+    /// Output is properly indented. Elements containing text or CDATA are
+    /// written on a single line, so that no whitespace is added to their
+    /// content.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::Io] for errors from writing to the Write object.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), XMLError> {
+        self.write_with(writer, WriteOptions::default())
+    }
+
+    /// Returns the XML document, where this element is the root element,
+    /// without whitespace between tags.
+    pub fn to_compact_string(&self) -> String {
+        format!("{:#}", self)
+    }
+
+    /// Outputs an XML document, where this element is the root element,
+    /// formatted and encoded according to `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::Unencodable] if a name contains characters that
+    /// cannot be represented in the chosen encoding, or [XMLError::Io] for
+    /// errors from writing to the Write object.
+    pub fn write_with<W: Write>(&self, writer: W, options: WriteOptions) -> Result<(), XMLError> {
+        let mut writer = XMLWriter::with_options(writer, options);
+        writer.write_element(self)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+fn check_comment(text: &str) -> Result<(), XMLError> {
+    if text.contains("--") || text.ends_with('-') || !text.chars().all(parser::is_xml_char) {
+        return Err(XMLError::InvalidComment {
+            comment: text.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+fn check_processing_instruction(target: &str, data: &str) -> Result<(), XMLError> {
+    if !is_valid_ncname(target)
+        || target.eq_ignore_ascii_case("xml")
+        || data.contains("?>")
+        || !data.chars().all(parser::is_xml_char)
+    {
+        return Err(XMLError::InvalidProcessingInstruction {
+            target: target.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+fn sanitize_comment(text: String) -> String {
+    let mut text = sanitize_chars(text);
+    while text.contains("--") {
+        text = text.replace("--", "- -");
+    }
+    if text.ends_with('-') {
+        text.push(' ');
+    }
+    text
 }
 
-fn escape_str(input: &str) -> String {
-    input
-        .replace('&', "&amp;")
-        .replace('"', "&quot;")
-        .replace('\'', "&apos;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
+/// Replaces characters not allowed in XML with U+FFFD REPLACEMENT CHARACTER.
+fn sanitize_chars(text: String) -> String {
+    if text.chars().all(parser::is_xml_char) {
+        return text;
+    }
+    text.chars()
+        .map(|c| {
+            if parser::is_xml_char(c) {
+                c
+            } else {
+                '\u{FFFD}'
+            }
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use XMLElement;
+    use {Encoding, WriteOptions, XMLDeclaration, XMLElement, XMLError, XMLNode};
 
     #[test]
     fn write_xml() {
@@ -244,7 +936,7 @@ mod tests {
         child4.add_text(6);
         root.add_child(child4);
 
-        let expected = r#"<?xml version = "1.0" encoding = "UTF-8"?>
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
 <root>
 	<child1>
 		<inner />
@@ -280,4 +972,399 @@ New line</inner>
         e.add_text("example text");
         e.add_child(XMLElement::new("test"));
     }
+
+    #[test]
+    fn accessors() {
+        let element: XMLElement =
+            "<a xmlns:x='urn:x' id='1 &amp; 2' x:k='v'>t &lt; u<x:b/><![CDATA[&c]]></a>"
+                .parse()
+                .unwrap();
+        assert_eq!(element.name(), "a");
+        assert_eq!(element.namespace(), None);
+        let attributes: Vec<_> = element.attributes().collect();
+        assert_eq!(attributes, [("id", "1 & 2"), ("k", "v")]);
+        assert_eq!(element.attribute("id").unwrap(), "1 & 2");
+        assert_eq!(element.attribute("k"), None);
+        assert_eq!(element.attribute_ns("urn:x", "k").unwrap(), "v");
+        let attributes: Vec<_> = element.attributes_ns().collect();
+        assert_eq!(
+            attributes,
+            [(None, "id", "1 & 2"), (Some("urn:x"), "k", "v")]
+        );
+        let mut built = XMLElement::new("a");
+        built.add_attribute_ns("urn:x", "k", 1);
+        built.add_attribute_ns("urn:y", "k", 2);
+        let attributes: Vec<_> = built.attributes_ns().collect();
+        assert_eq!(
+            attributes,
+            [(Some("urn:x"), "k", "1"), (Some("urn:y"), "k", "2")]
+        );
+        assert_eq!(element.text(), "t < u&c");
+        let child = element.children().next().unwrap();
+        assert_eq!((child.name(), child.namespace()), ("b", Some("urn:x")));
+        assert!(child.is_empty() && child.text().is_empty());
+        assert!(!element.is_empty());
+    }
+
+    #[test]
+    fn raw_values() {
+        let mut built = XMLElement::new("a");
+        built.add_attribute("q", "\"'");
+        built.add_text("<&>");
+        let parsed: XMLElement = "<a q='&quot;&apos;'>&lt;&amp;&gt;</a>".parse().unwrap();
+        assert_eq!(built, parsed);
+        assert_eq!(parsed.attribute("q"), Some("\"'"));
+        assert_eq!(parsed.text(), "<&>");
+        assert!(built
+            .to_string()
+            .contains("<a q=\"&quot;&apos;\">&lt;&amp;&gt;</a>"));
+    }
+
+    #[test]
+    fn add_child_to_indented() {
+        let mut list: XMLElement = "<list>\n  <item/>\n</list>".parse().unwrap();
+        list.add_child(XMLElement::new("item"));
+        assert_eq!(
+            list.to_compact_string(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><list>\n  <item/>\n<item/></list>"
+        );
+        let mut p: XMLElement = "<p> a </p>".parse().unwrap();
+        assert!(p.try_add_child(XMLElement::new("b")).is_err());
+    }
+
+    #[test]
+    fn invalid_chars_in_markup() {
+        let mut e = XMLElement::new("a\u{1}");
+        e.add_attribute("b\u{1}", "\u{1}");
+        e.add_comment("c\u{1}");
+        e.add_raw_unchecked("<d>\u{1}</d>");
+        assert_eq!(
+            e.to_compact_string(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <a\u{FFFD} b\u{FFFD}=\"\u{FFFD}\"><!--c\u{FFFD}--><d>\u{FFFD}</d></a\u{FFFD}>"
+        );
+        assert!(matches!(
+            e.try_add_comment("\u{1}"),
+            Err(XMLError::InvalidComment { .. })
+        ));
+        assert!(matches!(
+            e.try_add_processing_instruction("pi", "\u{1}"),
+            Err(XMLError::InvalidProcessingInstruction { .. })
+        ));
+        assert!(e.try_add_node(XMLNode::Raw("\u{1}".to_owned())).is_err());
+    }
+
+    #[test]
+    fn mutation() {
+        let mut element: XMLElement = "<a x='1' y='2'><b/><!--c--><c/><d/></a>".parse().unwrap();
+        assert_eq!(element.set_attribute("x", "<"), Some("1".to_owned()));
+        assert_eq!(element.set_attribute("z", 3), None);
+        assert_eq!(element.remove_attribute("y"), Some("2".to_owned()));
+        assert_eq!(element.remove_attribute("y"), None);
+
+        assert_eq!(element.remove_child(1).unwrap().name(), "c");
+        element.insert_child(1, XMLElement::new("e")).unwrap();
+        element.insert_child(3, XMLElement::new("f")).unwrap();
+        assert!(matches!(
+            element.insert_child(5, XMLElement::new("g")),
+            Err(XMLError::IndexOutOfBounds { index: 5, len: 4 })
+        ));
+        assert!(element.remove_child(4).is_err());
+        element.retain_children(|child| child.name() != "d");
+        for child in element.children_mut() {
+            child.add_attribute("seen", true);
+        }
+        assert_eq!(
+            element.to_compact_string(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><a x=\"&lt;\" z=\"3\">\
+             <b seen=\"true\"/><!--c--><e seen=\"true\"/><f seen=\"true\"/></a>"
+        );
+
+        element.replace_text("new");
+        assert_eq!(element.text(), "new");
+        element.insert_child(0, XMLElement::new("b")).unwrap();
+        assert_eq!(
+            element.to_compact_string(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><a x=\"&lt;\" z=\"3\">new<b/></a>"
+        );
+        element.clear_content();
+        assert!(element.is_empty());
+        element.add_child(XMLElement::new("b"));
+    }
+
+    #[test]
+    fn builder() {
+        let built = XMLElement::new("person")
+            .attr("id", 232)
+            .with_child("name", |name| name.with_text("Joe Schmoe"))
+            .child(XMLElement::new("hobbies").with_child("hobby", |hobby| hobby));
+        let mut person = XMLElement::new("person");
+        person.add_attribute("id", "232");
+        let mut name = XMLElement::new("name");
+        name.add_text("Joe Schmoe");
+        person.add_child(name);
+        let mut hobbies = XMLElement::new("hobbies");
+        hobbies.add_child(XMLElement::new("hobby"));
+        person.add_child(hobbies);
+        assert_eq!(built, person);
+    }
+
+    #[test]
+    fn write_mixed_content() {
+        let mut root = XMLElement::new("root");
+        let mut p = XMLElement::new("p");
+        p.add_node(XMLNode::Text("Hello ".to_owned()));
+        let mut b = XMLElement::new("b");
+        b.add_text("world");
+        p.add_node(b);
+        let mut i = XMLElement::new("i");
+        i.add_child(XMLElement::new("br"));
+        p.add_node(i);
+        p.add_node(XMLNode::Text("& goodbye!".to_owned()));
+        root.add_child(p);
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+	<p>Hello <b>world</b><i><br /></i>&amp; goodbye!</p>
+</root>
+"#;
+        assert_eq!(format!("{}", root), expected);
+    }
+
+    #[test]
+    fn try_add_conflicting_content() {
+        let mut e = XMLElement::new("test");
+        e.add_text("example text");
+        match e.try_add_child(XMLElement::new("child")) {
+            Err(XMLError::ContentConflict { element }) => assert_eq!(element, "test"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(e.try_add_text("more text").is_err());
+    }
+
+    #[test]
+    fn checked_names() {
+        assert!(XMLElement::try_new("1tag").is_err());
+        let mut e = XMLElement::try_new("tag").unwrap();
+        assert!(e.try_add_attribute("a b", "value").is_err());
+        assert!(e.try_add_attribute("xml:lang", "en").is_ok());
+    }
+
+    #[test]
+    fn write_namespaces() {
+        let mut root = XMLElement::new_ns("urn:a", "root");
+        root.set_namespace_prefix("urn:a", "");
+        let mut item = XMLElement::new_ns("urn:b", "item");
+        item.set_namespace_prefix("urn:b", "b");
+        item.add_attribute_ns("urn:b", "id", 1);
+        item.add_attribute_ns("urn:c", "x", 2);
+        item.add_attribute("plain", 3);
+        root.add_child(item);
+        let mut plain = XMLElement::new("plain");
+        let mut inner = XMLElement::new_ns("urn:a", "inner");
+        inner.add_attribute_ns("http://www.w3.org/XML/1998/namespace", "lang", "en");
+        plain.add_child(inner);
+        root.add_child(plain);
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root xmlns="urn:a" xmlns:b="urn:b" xmlns:ns0="urn:c">
+	<b:item b:id="1" ns0:x="2" plain="3" />
+	<plain xmlns="">
+		<inner xmlns="urn:a" xml:lang="en" />
+	</plain>
+</root>
+"#;
+        assert_eq!(format!("{}", root), expected);
+
+        let mut literal = XMLElement::new("ns0:x");
+        literal.add_attribute("xmlns:ns0", "urn:y");
+        literal.add_attribute("xmlns:ns1", "urn:w");
+        literal.add_attribute_ns("urn:z", "q", 1);
+        literal.set_namespace_prefix("urn:z", "a b");
+        assert_eq!(
+            literal.to_compact_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?><ns0:x xmlns:ns2="urn:z" xmlns:ns0="urn:y" xmlns:ns1="urn:w" ns2:q="1"/>"#
+        );
+
+        let mut literal = XMLElement::new_ns("urn:a", "a");
+        literal.set_namespace_prefix("urn:a", "");
+        literal.add_attribute("xmlns", "urn:z");
+        assert_eq!(
+            literal.to_compact_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?><ns0:a xmlns:ns0="urn:a" xmlns="urn:z"/>"#
+        );
+    }
+
+    #[test]
+    fn write_compact() {
+        let mut root = XMLElement::new("root");
+        let mut child = XMLElement::new("child");
+        child.add_attribute("a", 1);
+        child.add_child(XMLElement::new("empty"));
+        root.add_child(child);
+        let mut text = XMLElement::new("text");
+        text.add_text(" spaced\n text ");
+        root.add_child(text);
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?><root><child a="1"><empty/></child><text> spaced
+ text </text></root>"#;
+        assert_eq!(root.to_compact_string(), expected);
+        assert_eq!(format!("{:#}", root), expected);
+    }
+
+    #[test]
+    fn write_declaration() {
+        let root = XMLElement::new("root");
+        let options = WriteOptions::new().declaration(Some(
+            XMLDeclaration::new()
+                .encoding("utf-8")
+                .standalone(Some(false)),
+        ));
+        let mut output = Vec::new();
+        root.write_with(&mut output, options).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"no\"?>\n<root />\n"
+        );
+
+        let mut output = Vec::new();
+        root.write_with(&mut output, WriteOptions::new().declaration(None))
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "<root />\n");
+    }
+
+    #[test]
+    fn write_encoded() {
+        let mut root = XMLElement::new("café");
+        root.add_attribute("price", "5€");
+        root.add_text("naïve \u{2603}");
+        let mut output = Vec::new();
+        root.write_with(
+            &mut output,
+            WriteOptions::new().encoding(Encoding::Iso8859_1),
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            &b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<caf\xE9 price=\"5&#x20AC;\">na\xEFve &#x2603;</caf\xE9>\n"[..]
+        );
+    }
+
+    #[test]
+    fn write_cdata() {
+        let mut root = XMLElement::new("root");
+        let mut script = XMLElement::new("script");
+        script.add_cdata("if (a < b && c) { x[y[0]]>1 }");
+        root.add_child(script);
+        let mut p = XMLElement::new("p");
+        p.add_node(XMLNode::Text("<".to_owned()));
+        p.add_cdata("<");
+        root.add_child(p);
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+	<script><![CDATA[if (a < b && c) { x[y[0]]]]><![CDATA[>1 }]]></script>
+	<p>&lt;<![CDATA[<]]></p>
+</root>
+"#;
+        assert_eq!(format!("{}", root), expected);
+    }
+
+    #[test]
+    fn write_comments() {
+        let mut root = XMLElement::new("root");
+        root.add_comment(" settings --- see docs -");
+        root.add_child(XMLElement::new("setting"));
+        assert!(root.try_add_comment("a--b").is_err());
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+	<!-- settings - - - see docs - -->
+	<setting />
+</root>
+"#;
+        assert_eq!(format!("{}", root), expected);
+    }
+
+    #[test]
+    fn write_processing_instructions() {
+        let mut root = XMLElement::new("root");
+        root.add_processing_instruction("app", "");
+        let mut p = XMLElement::new("p");
+        p.add_text("a");
+        p.add_processing_instruction("break", "page=\"1\"");
+        root.add_child(p);
+        assert!(root.try_add_processing_instruction("XML", "").is_err());
+        assert!(root.try_add_processing_instruction("a b", "").is_err());
+        assert!(root.try_add_processing_instruction("app", "?>").is_err());
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+	<?app?>
+	<p>a<?break page="1"?></p>
+</root>
+"#;
+        assert_eq!(format!("{}", root), expected);
+    }
+
+    #[test]
+    fn add_invalid_nodes() {
+        let mut element = XMLElement::new("a");
+        let reserved = XMLNode::ProcessingInstruction {
+            target: "xml".to_owned(),
+            data: String::new(),
+        };
+        assert!(matches!(
+            element.try_add_node(reserved),
+            Err(XMLError::InvalidProcessingInstruction { .. })
+        ));
+        assert!(element
+            .try_add_node(XMLNode::Comment("a--b".to_owned()))
+            .is_err());
+        element.add_node(XMLNode::Comment("a--b".to_owned()));
+        assert_eq!(element.content.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn add_reserved_processing_instruction_node() {
+        XMLElement::new("a").add_node(XMLNode::ProcessingInstruction {
+            target: "xml".to_owned(),
+            data: String::new(),
+        });
+    }
+
+    #[test]
+    fn write_raw() {
+        let mut root = XMLElement::new("root");
+        let mut body = XMLElement::new("body");
+        body.add_raw_unchecked("<p>&nbsp;</p>");
+        body.try_add_raw("<x:a/>text<!-- c --><b>1</b>").unwrap();
+        root.add_child(body);
+        root.add_child(XMLElement::new("after"));
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+	<body><p>&nbsp;</p><x:a/>text<!-- c --><b>1</b></body>
+	<after />
+</root>
+"#;
+        assert_eq!(format!("{}", root), expected);
+
+        let mut element = XMLElement::new("a");
+        for fragment in &[
+            "<a>",
+            "</a>",
+            "<a></b>",
+            "a & b",
+            "<!DOCTYPE a>",
+            "<?xml version='1.0'?>",
+        ] {
+            assert!(
+                matches!(element.try_add_raw(fragment), Err(XMLError::Parse { .. })),
+                "{}",
+                fragment
+            );
+        }
+        assert!(element.is_empty());
+    }
 }
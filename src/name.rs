@@ -0,0 +1,157 @@
+use {sanitize_chars, XMLError};
+
+/// An element or attribute name, optionally qualified by a namespace URI.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub(crate) struct QName {
+    pub(crate) namespace: Option<String>,
+    pub(crate) local: String,
+}
+
+/// Characters not allowed in XML are replaced in local names, since names are
+/// written as markup, where they cannot be escaped.
+impl QName {
+    pub(crate) fn new(name: String) -> Self {
+        QName {
+            namespace: None,
+            local: sanitize_chars(name),
+        }
+    }
+
+    pub(crate) fn new_ns(namespace: String, local: String) -> Self {
+        QName {
+            namespace: Some(namespace),
+            local: sanitize_chars(local),
+        }
+    }
+}
+
+/// Returns whether `c` matches the XML 1.0 `NameStartChar` production.
+pub(crate) fn is_name_start_char(c: char) -> bool {
+    matches!(
+        c,
+        ':' | 'A'..='Z'
+        | '_'
+        | 'a'..='z'
+        | '\u{C0}'..='\u{D6}'
+        | '\u{D8}'..='\u{F6}'
+        | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}'
+        | '\u{37F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}'
+        | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}'
+        | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}'
+    )
+}
+
+/// Returns whether `c` matches the XML 1.0 `NameChar` production.
+pub(crate) fn is_name_char(c: char) -> bool {
+    match c {
+        '-' | '.' | '0'..='9' | '\u{B7}' => true,
+        '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}' => true,
+        c => is_name_start_char(c),
+    }
+}
+
+/// Returns whether `name` matches the XML 1.0 `Name` production.
+///
+/// ```rust
+/// use simple_xml_builder::is_valid_name;
+///
+/// assert!(is_valid_name("person"));
+/// assert!(is_valid_name("xs:élément"));
+/// assert!(!is_valid_name("a b"));
+/// assert!(!is_valid_name("1tag"));
+/// ```
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if is_name_start_char(c) => chars.all(is_name_char),
+        _ => false,
+    }
+}
+
+/// Returns whether `name` matches the `NCName` production from Namespaces in
+/// XML 1.0, i.e. a `Name` without colons.
+pub fn is_valid_ncname(name: &str) -> bool {
+    !name.contains(':') && is_valid_name(name)
+}
+
+/// Checks a qualified name, returning its prefix if valid.
+fn split_qname(name: &str) -> Option<(Option<&str>, &str)> {
+    match name.find(':') {
+        None if is_valid_ncname(name) => Some((None, name)),
+        Some(i) if is_valid_ncname(&name[..i]) && is_valid_ncname(&name[i + 1..]) => {
+            Some((Some(&name[..i]), &name[i + 1..]))
+        }
+        _ => None,
+    }
+}
+
+fn invalid(name: &str) -> XMLError {
+    XMLError::InvalidName {
+        name: name.to_owned(),
+    }
+}
+
+/// Checks that `name` may be used as the local part of a namespace-qualified
+/// name.
+pub(crate) fn check_local_name(name: &str) -> Result<(), XMLError> {
+    if is_valid_ncname(name) {
+        Ok(())
+    } else {
+        Err(invalid(name))
+    }
+}
+
+/// Checks that `name` may be used as an element name.
+pub(crate) fn check_element_name(name: &str) -> Result<(), XMLError> {
+    match split_qname(name) {
+        Some((Some("xmlns"), _)) | None => Err(invalid(name)),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Checks that `name` may be used as an attribute name. Unlike element names,
+/// this allows namespace declarations.
+pub(crate) fn check_attribute_name(name: &str) -> Result<(), XMLError> {
+    match split_qname(name) {
+        Some(_) => Ok(()),
+        None => Err(invalid(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("_a-b.c"));
+        assert!(is_valid_name("\u{4E2D}\u{6587}"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("x<y"));
+        assert!(!is_valid_name("-a"));
+        assert!(!is_valid_ncname("a:b"));
+    }
+
+    #[test]
+    fn reserved_names() {
+        assert!(check_element_name("a:b").is_ok());
+        assert!(check_element_name("a:b:c").is_err());
+        assert!(check_element_name("XMLdata").is_ok());
+        assert!(check_element_name("xmlData").is_ok());
+        assert!(check_element_name("xml:a").is_ok());
+        assert!(check_element_name("xmlns:a").is_err());
+        assert!(check_attribute_name("xmlns").is_ok());
+        assert!(check_attribute_name("xmlns:a").is_ok());
+        assert!(check_attribute_name("xml:lang").is_ok());
+        assert!(check_attribute_name("xmlfoo").is_ok());
+        assert!(check_element_name("ééé").is_ok());
+        assert!(check_attribute_name("aé").is_ok());
+        assert!(check_element_name("xé:a").is_ok());
+    }
+}
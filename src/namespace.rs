@@ -0,0 +1,151 @@
+use indexmap::IndexMap;
+use name::{is_valid_ncname, QName};
+use {XMLElement, XMLNode};
+
+/// The namespace bound to the reserved `xml` prefix.
+pub(crate) const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Prefix assignments for the namespaces used or given a prefix in an element
+/// tree.
+///
+/// All prefixes are declared on the root element of the tree. The default
+/// namespace is declared on the outermost elements using it, and undeclared on
+/// elements with no namespace within its scope.
+pub(crate) struct Namespaces {
+    prefixes: IndexMap<String, String>,
+    default: Option<String>,
+}
+
+impl Namespaces {
+    /// Assigns prefixes to the namespaces used in the tree rooted at `root`,
+    /// honoring preferences set with
+    /// [set_namespace_prefix](XMLElement::set_namespace_prefix) where possible.
+    pub(crate) fn new(root: &XMLElement) -> Self {
+        let mut preferred = IndexMap::new();
+        let mut literal = Vec::new();
+        let mut literal_default = false;
+        visit(root, &mut |elem| {
+            for (uri, prefix) in &elem.namespace_prefixes {
+                preferred
+                    .entry(uri.clone())
+                    .or_insert_with(|| prefix.clone());
+            }
+            if elem.name.namespace.is_none() {
+                literal.extend(literal_prefix(&elem.name.local));
+            }
+            for name in elem.attributes.keys() {
+                match &name.namespace {
+                    Some(_) => {}
+                    None if name.local == "xmlns" => literal_default = true,
+                    None => literal.extend(literal_prefix(&name.local)),
+                }
+            }
+        });
+        // A literal `xmlns` attribute would conflict with declaring the
+        // default namespace, so its namespace is given a prefix instead.
+        let default = preferred
+            .iter()
+            .find(|(_, prefix)| prefix.is_empty() && !literal_default)
+            .map(|(uri, _)| uri.clone());
+
+        let mut needed: IndexMap<String, ()> = IndexMap::new();
+        visit(root, &mut |elem| {
+            for (uri, prefix) in &elem.namespace_prefixes {
+                if !prefix.is_empty() {
+                    needed.insert(uri.clone(), ());
+                }
+            }
+            match &elem.name.namespace {
+                Some(uri) if default.as_ref() != Some(uri) => {
+                    needed.insert(uri.clone(), ());
+                }
+                _ => {}
+            }
+            for name in elem.attributes.keys() {
+                if let Some(uri) = &name.namespace {
+                    needed.insert(uri.clone(), ());
+                }
+            }
+        });
+
+        let mut prefixes = IndexMap::new();
+        let mut generated = 0;
+        for (uri, _) in needed {
+            let prefix = if uri == XML_NAMESPACE {
+                "xml".to_owned()
+            } else {
+                match preferred.get(&uri) {
+                    Some(prefix) if is_usable(prefix, &prefixes, &literal) => prefix.clone(),
+                    _ => loop {
+                        let prefix = format!("ns{}", generated);
+                        generated += 1;
+                        if is_usable(&prefix, &prefixes, &literal) {
+                            break prefix;
+                        }
+                    },
+                }
+            };
+            prefixes.insert(uri, prefix);
+        }
+        Namespaces { prefixes, default }
+    }
+
+    /// Returns the namespace declared as the default namespace, if any.
+    pub(crate) fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    /// Returns the prefix declarations to write on the root element, as
+    /// `(prefix, uri)` pairs.
+    pub(crate) fn declarations(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.prefixes
+            .iter()
+            .filter(|(uri, _)| *uri != XML_NAMESPACE)
+            .map(|(uri, prefix)| (prefix.as_str(), uri.as_str()))
+    }
+
+    /// Returns the name to write for an element.
+    pub(crate) fn element_name(&self, name: &QName) -> String {
+        match &name.namespace {
+            Some(uri) if self.default.as_ref() == Some(uri) => name.local.clone(),
+            _ => self.attribute_name(name),
+        }
+    }
+
+    /// Returns the name to write for an attribute.
+    pub(crate) fn attribute_name(&self, name: &QName) -> String {
+        match &name.namespace {
+            Some(uri) => format!("{}:{}", self.prefixes[uri], name.local),
+            None => name.local.clone(),
+        }
+    }
+}
+
+/// Returns whether `prefix` can be declared for a namespace, given the
+/// prefixes already assigned and those written literally in names.
+fn is_usable(prefix: &str, taken: &IndexMap<String, String>, literal: &[String]) -> bool {
+    is_valid_ncname(prefix)
+        && prefix != "xml"
+        && prefix != "xmlns"
+        && taken.values().all(|other| other != prefix)
+        && literal.iter().all(|other| other != prefix)
+}
+
+/// Returns the prefix used by a name given without a namespace, such as `p`
+/// in `p:name` or `xmlns:p`.
+fn literal_prefix(name: &str) -> Option<String> {
+    let (prefix, local) = name.split_at(name.find(':')?);
+    match prefix {
+        "xmlns" => Some(local[1..].to_owned()),
+        _ => Some(prefix.to_owned()),
+    }
+}
+
+fn visit<F: FnMut(&XMLElement)>(elem: &XMLElement, f: &mut F) {
+    f(elem);
+    for node in &elem.content {
+        if let XMLNode::Element(child) = node {
+            visit(child, f);
+        }
+    }
+}
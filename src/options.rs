@@ -0,0 +1,396 @@
+use Encoding;
+
+/// Options controlling how XML output is formatted.
+///
+/// The default options produce the same output as
+/// [XMLElement::write](::XMLElement::write): UTF-8 output, the default
+/// [XMLDeclaration], tab indentation, `\n` line endings, `<tag />` for empty elements, a
+/// trailing newline, and characters not allowed in XML replaced with U+FFFD.
+///
+/// ```rust
+/// use simple_xml_builder::{EmptyElement, Indent, LineEnding, WriteOptions};
+///
+/// let options = WriteOptions::new()
+///     .indent(Indent::Spaces(2))
+///     .line_ending(LineEnding::CrLf)
+///     .empty_element(EmptyElement::SelfClosing)
+///     .trailing_newline(false);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WriteOptions {
+    encoding: Encoding,
+    ascii_only: bool,
+    declaration: Option<XMLDeclaration>,
+    pretty: bool,
+    indent: Indent,
+    line_ending: LineEnding,
+    empty_element: EmptyElement,
+    trailing_newline: bool,
+    invalid_chars: InvalidChars,
+    escaping: Escaping,
+    attribute_quote: AttributeQuote,
+}
+
+/// The XML declaration written at the start of a document.
+///
+/// The default declaration is `<?xml version="1.0" encoding="UTF-8"?>`, with
+/// the encoding label matching the [Encoding] being written. Values are
+/// written verbatim.
+///
+/// ```rust
+/// use simple_xml_builder::{WriteOptions, XMLDeclaration};
+///
+/// // <?xml version="1.0" standalone="yes"?>
+/// let options = WriteOptions::new().declaration(Some(
+///     XMLDeclaration::new().without_encoding().standalone(Some(true)),
+/// ));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct XMLDeclaration {
+    version: String,
+    encoding: EncodingLabel,
+    standalone: Option<bool>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum EncodingLabel {
+    Auto,
+    Custom(String),
+    Omitted,
+}
+
+impl Default for XMLDeclaration {
+    fn default() -> Self {
+        XMLDeclaration {
+            version: "1.0".to_owned(),
+            encoding: EncodingLabel::Auto,
+            standalone: None,
+        }
+    }
+}
+
+impl XMLDeclaration {
+    /// Creates the default declaration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the version label.
+    pub fn version(mut self, version: impl ToString) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// Sets the encoding label, instead of using the label of the [Encoding]
+    /// being written.
+    pub fn encoding(mut self, encoding: impl ToString) -> Self {
+        self.encoding = EncodingLabel::Custom(encoding.to_string());
+        self
+    }
+
+    /// Leaves the encoding label out of the declaration.
+    pub fn without_encoding(mut self) -> Self {
+        self.encoding = EncodingLabel::Omitted;
+        self
+    }
+
+    /// Sets the `standalone` pseudo-attribute, or leaves it out if `None`.
+    pub fn standalone(mut self, standalone: Option<bool>) -> Self {
+        self.standalone = standalone;
+        self
+    }
+
+    /// Returns the declaration as written for a document in `encoding`.
+    pub(crate) fn to_xml(&self, encoding: Encoding) -> String {
+        let mut result = format!(r#"<?xml version="{}""#, self.version);
+        match &self.encoding {
+            EncodingLabel::Auto => result += &format!(r#" encoding="{}""#, encoding.label()),
+            EncodingLabel::Custom(label) => result += &format!(r#" encoding="{}""#, label),
+            EncodingLabel::Omitted => {}
+        }
+        if let Some(standalone) = self.standalone {
+            let value = if standalone { "yes" } else { "no" };
+            result += &format!(r#" standalone="{}""#, value);
+        }
+        result + "?>"
+    }
+}
+
+/// Indentation added for each level of nesting.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Indent {
+    /// No indentation. Elements are still written on separate lines.
+    None,
+    /// One tab per level.
+    Tab,
+    /// The given number of spaces per level.
+    Spaces(usize),
+}
+
+/// Line ending written between lines of output.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+/// How elements without content are written.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EmptyElement {
+    /// `<tag/>`
+    SelfClosing,
+    /// `<tag />`
+    SelfClosingSpace,
+    /// `<tag></tag>`
+    Expanded,
+}
+
+/// How characters that XML 1.0 does not allow, such as most control
+/// characters, are handled in text, attribute values and CDATA sections. They
+/// cannot be written even as character references. In markup, such as names,
+/// comments and processing instructions, they are always an error.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InvalidChars {
+    /// Fail with [XMLError::InvalidCharacter](::XMLError::InvalidCharacter).
+    Error,
+    /// Leave the characters out.
+    Strip,
+    /// Write the given character instead. The replacement must itself be
+    /// allowed.
+    Replace(char),
+}
+
+/// Which characters are escaped in text and attribute values.
+///
+/// In both profiles, tabs, line feeds and carriage returns in attribute values
+/// are written as character references so they survive parsing.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Escaping {
+    /// Escape only where required: `&` and `<`, `>` following `]]` in text,
+    /// and the quote character in attribute values.
+    Minimal,
+    /// Escape `&`, `<`, `>`, `"` and `'` everywhere.
+    Full,
+}
+
+/// The quote character written around attribute values.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AttributeQuote {
+    /// `key="value"`
+    Double,
+    /// `key='value'`
+    Single,
+    /// Whichever quote occurs less often in each value, so it needs less
+    /// escaping. Double quotes are used if both occur equally often.
+    Auto,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            encoding: Encoding::Utf8,
+            ascii_only: false,
+            declaration: Some(XMLDeclaration::default()),
+            pretty: true,
+            indent: Indent::Tab,
+            line_ending: LineEnding::Lf,
+            empty_element: EmptyElement::SelfClosingSpace,
+            trailing_newline: true,
+            invalid_chars: InvalidChars::Replace('\u{FFFD}'),
+            escaping: Escaping::Full,
+            attribute_quote: AttributeQuote::Double,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the character encoding of the output.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets whether all non-ASCII characters in text and attribute values are
+    /// written as numeric character references (`&#xE9;`), producing pure
+    /// 7-bit output. Names containing non-ASCII characters cause
+    /// [XMLError::Unencodable](::XMLError::Unencodable).
+    pub fn ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    /// Sets the XML declaration, or leaves it out if `None`.
+    pub fn declaration(mut self, declaration: Option<XMLDeclaration>) -> Self {
+        self.declaration = declaration;
+        self
+    }
+
+    /// Creates options for compact output, with no whitespace between tags,
+    /// `<tag/>` for empty elements, and no trailing newline.
+    ///
+    /// Text content is written unchanged.
+    pub fn compact() -> Self {
+        Self::default()
+            .pretty(false)
+            .empty_element(EmptyElement::SelfClosing)
+            .trailing_newline(false)
+    }
+
+    /// Sets whether line breaks and indentation are added between tags. When
+    /// disabled, the indent and line ending settings only affect the trailing
+    /// newline.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Sets the indentation used for each level of nesting.
+    pub fn indent(mut self, indent: Indent) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Sets the line ending.
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Sets how elements without content are written.
+    pub fn empty_element(mut self, empty_element: EmptyElement) -> Self {
+        self.empty_element = empty_element;
+        self
+    }
+
+    /// Sets whether a line ending is written after the root element.
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Sets how characters not allowed in XML are handled. By default they are
+    /// replaced with U+FFFD REPLACEMENT CHARACTER.
+    ///
+    /// ```rust
+    /// use simple_xml_builder::{InvalidChars, WriteOptions, XMLElement};
+    ///
+    /// let mut element = XMLElement::new("log");
+    /// element.add_text("\u{1B}[1mbold");
+    /// let mut output = Vec::new();
+    /// let options = WriteOptions::compact().invalid_chars(InvalidChars::Strip);
+    /// element.write_with(&mut output, options).unwrap();
+    /// assert!(output.ends_with(b"<log>[1mbold</log>"));
+    /// ```
+    pub fn invalid_chars(mut self, invalid_chars: InvalidChars) -> Self {
+        self.invalid_chars = invalid_chars;
+        self
+    }
+
+    /// Sets which characters are escaped in text and attribute values. The
+    /// default is [Escaping::Full].
+    ///
+    /// ```rust
+    /// use simple_xml_builder::{Escaping, WriteOptions, XMLElement};
+    ///
+    /// let mut element = XMLElement::new("q");
+    /// element.add_attribute("by", "O'Brien");
+    /// element.add_text("\"Don't\" > \"do\"");
+    /// let mut output = Vec::new();
+    /// let options = WriteOptions::compact().escaping(Escaping::Minimal);
+    /// element.write_with(&mut output, options).unwrap();
+    /// assert!(output.ends_with(br#"<q by="O'Brien">"Don't" > "do"</q>"#));
+    /// ```
+    pub fn escaping(mut self, escaping: Escaping) -> Self {
+        self.escaping = escaping;
+        self
+    }
+
+    /// Sets the quote character written around attribute values. The default
+    /// is [AttributeQuote::Double].
+    ///
+    /// ```rust
+    /// use simple_xml_builder::{AttributeQuote, Escaping, WriteOptions, XMLElement};
+    ///
+    /// let mut element = XMLElement::new("a");
+    /// element.add_attribute("title", r#"Say "hi""#);
+    /// let mut output = Vec::new();
+    /// let options = WriteOptions::compact()
+    ///     .escaping(Escaping::Minimal)
+    ///     .attribute_quote(AttributeQuote::Auto);
+    /// element.write_with(&mut output, options).unwrap();
+    /// assert!(output.ends_with(br#"<a title='Say "hi"'/>"#));
+    /// ```
+    pub fn attribute_quote(mut self, attribute_quote: AttributeQuote) -> Self {
+        self.attribute_quote = attribute_quote;
+        self
+    }
+
+    pub(crate) fn output_encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub(crate) fn is_ascii_only(&self) -> bool {
+        self.ascii_only
+    }
+
+    pub(crate) fn invalid_chars_policy(&self) -> InvalidChars {
+        self.invalid_chars
+    }
+
+    pub(crate) fn escaping_profile(&self) -> Escaping {
+        self.escaping
+    }
+
+    /// Returns the quote character to use for an attribute value.
+    pub(crate) fn quote_for(&self, value: &str) -> char {
+        match self.attribute_quote {
+            AttributeQuote::Double => '"',
+            AttributeQuote::Single => '\'',
+            AttributeQuote::Auto => {
+                if value.matches('\'').count() < value.matches('"').count() {
+                    '\''
+                } else {
+                    '"'
+                }
+            }
+        }
+    }
+
+    pub(crate) fn xml_declaration(&self) -> Option<&XMLDeclaration> {
+        self.declaration.as_ref()
+    }
+
+    pub(crate) fn is_pretty(&self) -> bool {
+        self.pretty
+    }
+
+    pub(crate) fn indent_str(&self, level: usize) -> String {
+        match self.indent {
+            Indent::None => String::new(),
+            Indent::Tab => "\t".repeat(level),
+            Indent::Spaces(n) => " ".repeat(n * level),
+        }
+    }
+
+    pub(crate) fn newline_str(&self) -> &'static str {
+        match self.line_ending {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    pub(crate) fn empty_element_style(&self) -> EmptyElement {
+        self.empty_element
+    }
+
+    pub(crate) fn has_trailing_newline(&self) -> bool {
+        self.trailing_newline
+    }
+}
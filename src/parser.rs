@@ -0,0 +1,974 @@
+use name::{is_name_char, is_name_start_char};
+use namespace::XML_NAMESPACE;
+use std::borrow::Cow;
+use {
+    Encoding, XMLDeclaration, XMLDoctype, XMLDocument, XMLElement, XMLError, XMLEvent, XMLNode,
+    XMLReader,
+};
+
+/// Options controlling how XML text is parsed.
+///
+/// Entity declarations in the document type declaration, references to
+/// parameter entities and references to entities other than the predefined
+/// ones (`&lt;`, `&gt;`, `&amp;`, `&apos;`, `&quot;`) are always rejected, so
+/// parsing is not vulnerable to entity expansion attacks. External DTDs are
+/// never loaded. Elements may only be nested up to a
+/// [maximum depth](ParseOptions::max_depth).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseOptions {
+    keep_whitespace: bool,
+    max_depth: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            keep_whitespace: true,
+            max_depth: 256,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether text consisting only of whitespace is kept, which it is by
+    /// default. If not, it is dropped from elements containing no other text,
+    /// where it is usually indentation, which is added back when writing.
+    /// Whitespace in elements that also contain other text is always kept.
+    pub fn keep_whitespace(mut self, keep_whitespace: bool) -> Self {
+        self.keep_whitespace = keep_whitespace;
+        self
+    }
+
+    /// Sets how deeply elements may be nested, counting the root element.
+    /// Deeper documents are rejected, since elements are written, cloned and
+    /// dropped recursively. The default is 256.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+}
+
+/// A lexical unit of an XML document.
+#[derive(Debug)]
+pub(crate) enum Token<'a> {
+    Declaration {
+        version: &'a str,
+        encoding: Option<&'a str>,
+        standalone: Option<bool>,
+    },
+    Doctype(XMLDoctype),
+    StartTag {
+        name: &'a str,
+        attributes: Vec<(&'a str, Cow<'a, str>)>,
+        empty: bool,
+    },
+    EndTag {
+        name: &'a str,
+    },
+    Text(Cow<'a, str>),
+    CData(Cow<'a, str>),
+    Comment(Cow<'a, str>),
+    ProcessingInstruction {
+        target: &'a str,
+        data: Cow<'a, str>,
+    },
+}
+
+/// Splits XML text into tokens, decoding references in text and attribute
+/// values.
+pub(crate) struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+    start: usize,
+    at_start: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        let pos = if input.starts_with('\u{FEFF}') { 3 } else { 0 };
+        Tokenizer {
+            input,
+            pos,
+            start: pos,
+            at_start: true,
+        }
+    }
+
+    /// Creates a tokenizer for part of a document. An XML declaration is only
+    /// accepted if `at_start` is set.
+    pub(crate) fn chunk(input: &'a str, at_start: bool) -> Self {
+        Tokenizer {
+            input,
+            pos: 0,
+            start: 0,
+            at_start,
+        }
+    }
+
+    /// Returns an error located at the start of the last token.
+    pub(crate) fn token_error(&self, message: impl ToString) -> XMLError {
+        self.error(self.start, message)
+    }
+
+    /// Returns an error located at the end of the input.
+    pub(crate) fn end_error(&self, message: impl ToString) -> XMLError {
+        self.error(self.input.len(), message)
+    }
+
+    /// Returns the next token, or `None` at the end of the input.
+    pub(crate) fn next_token(&mut self) -> Result<Option<Token<'a>>, XMLError> {
+        self.start = self.pos;
+        let at_start = self.at_start;
+        self.at_start = false;
+        let rest = self.rest();
+        if rest.is_empty() {
+            Ok(None)
+        } else if rest.starts_with("<?") {
+            self.processing_instruction(at_start).map(Some)
+        } else if rest.starts_with("<!--") {
+            self.comment().map(Some)
+        } else if rest.starts_with("<![CDATA[") {
+            self.pos += "<![CDATA[".len();
+            let start = self.pos;
+            let text = self.until("]]>", "CDATA section")?;
+            self.check_chars(text, start)?;
+            Ok(Some(Token::CData(normalize_newlines(text))))
+        } else if rest.starts_with("<!DOCTYPE") {
+            self.doctype().map(Some)
+        } else if rest.starts_with("</") {
+            self.pos += 2;
+            let name = self.name()?;
+            self.skip_whitespace();
+            self.expect(">")?;
+            Ok(Some(Token::EndTag { name }))
+        } else if rest.starts_with('<') {
+            self.start_tag().map(Some)
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..end];
+            if let Some(i) = text.find("]]>") {
+                return Err(self.error(self.pos + i, "`]]>` is not allowed in text"));
+            }
+            let text = self.decode(text, self.pos, false)?;
+            self.pos += end;
+            Ok(Some(Token::Text(text)))
+        }
+    }
+
+    fn start_tag(&mut self) -> Result<Token<'a>, XMLError> {
+        self.pos += 1;
+        let name = self.name()?;
+        let mut attributes = Vec::new();
+        loop {
+            let separated = self.skip_whitespace();
+            if self.eat("/>") {
+                return Ok(Token::StartTag {
+                    name,
+                    attributes,
+                    empty: true,
+                });
+            }
+            if self.eat(">") {
+                return Ok(Token::StartTag {
+                    name,
+                    attributes,
+                    empty: false,
+                });
+            }
+            if !separated {
+                return Err(self.error(self.pos, "expected whitespace, `>` or `/>`"));
+            }
+            let attribute_pos = self.pos;
+            let attribute = self.name()?;
+            if attributes.iter().any(|(name, _)| *name == attribute) {
+                let message = format!("duplicate attribute `{}`", attribute);
+                return Err(self.error(attribute_pos, message));
+            }
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let value_pos = self.pos + 1;
+            let value = self.quoted()?;
+            if let Some(i) = value.find('<') {
+                return Err(self.error(value_pos + i, "`<` is not allowed in attribute values"));
+            }
+            attributes.push((attribute, self.decode(value, value_pos, true)?));
+        }
+    }
+
+    fn comment(&mut self) -> Result<Token<'a>, XMLError> {
+        self.pos += "<!--".len();
+        let start = self.pos;
+        let text = self.until("-->", "comment")?;
+        self.check_chars(text, start)?;
+        if let Some(i) = text.find("--") {
+            return Err(self.error(start + i, "`--` is not allowed in comments"));
+        }
+        if text.ends_with('-') {
+            return Err(self.error(start + text.len() - 1, "comments may not end with `-`"));
+        }
+        Ok(Token::Comment(normalize_newlines(text)))
+    }
+
+    fn processing_instruction(&mut self, at_start: bool) -> Result<Token<'a>, XMLError> {
+        let start = self.pos;
+        self.pos += 2;
+        let target = self.name()?;
+        if target.eq_ignore_ascii_case("xml") {
+            if target == "xml" && at_start {
+                return self.declaration();
+            }
+            return Err(self.error(start, "XML declaration is only allowed at the start"));
+        }
+        if target.contains(':') {
+            return Err(self.error(
+                start + 2,
+                "processing instruction targets may not contain `:`",
+            ));
+        }
+        if self.eat("?>") {
+            return Ok(Token::ProcessingInstruction {
+                target,
+                data: Cow::Borrowed(""),
+            });
+        }
+        if !self.skip_whitespace() {
+            return Err(self.error(self.pos, "expected whitespace or `?>`"));
+        }
+        let data_start = self.pos;
+        let data = self.until("?>", "processing instruction")?;
+        self.check_chars(data, data_start)?;
+        Ok(Token::ProcessingInstruction {
+            target,
+            data: normalize_newlines(data),
+        })
+    }
+
+    fn declaration(&mut self) -> Result<Token<'a>, XMLError> {
+        let mut version = None;
+        let mut encoding = None;
+        let mut standalone = None;
+        loop {
+            let separated = self.skip_whitespace();
+            if self.eat("?>") {
+                break;
+            }
+            let pos = self.pos;
+            if !separated {
+                return Err(self.error(pos, "expected whitespace or `?>`"));
+            }
+            let name = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let value = self.quoted()?;
+            match name {
+                "version" if version.is_none() && encoding.is_none() => version = Some(value),
+                "encoding" if version.is_some() && encoding.is_none() && standalone.is_none() => {
+                    encoding = Some(value)
+                }
+                "standalone" if version.is_some() && standalone.is_none() => {
+                    standalone = match value {
+                        "yes" => Some(true),
+                        "no" => Some(false),
+                        _ => return Err(self.error(pos, "standalone must be `yes` or `no`")),
+                    }
+                }
+                _ => {
+                    return Err(self.error(pos, format!("unexpected `{}` in XML declaration", name)))
+                }
+            }
+        }
+        match version {
+            Some(version) => Ok(Token::Declaration {
+                version,
+                encoding,
+                standalone,
+            }),
+            None => Err(self.token_error("XML declaration is missing the version")),
+        }
+    }
+
+    fn doctype(&mut self) -> Result<Token<'a>, XMLError> {
+        self.pos += "<!DOCTYPE".len();
+        if !self.skip_whitespace() {
+            return Err(self.error(self.pos, "expected whitespace"));
+        }
+        let mut doctype = XMLDoctype::new(self.name()?);
+        self.skip_whitespace();
+        if self.eat("SYSTEM") {
+            self.skip_whitespace();
+            doctype = doctype.system_id(self.quoted()?);
+        } else if self.eat("PUBLIC") {
+            self.skip_whitespace();
+            let public_id = self.quoted()?;
+            self.skip_whitespace();
+            doctype = doctype.public_id(public_id, self.quoted()?);
+        }
+        self.skip_whitespace();
+        if self.eat("[") {
+            loop {
+                self.skip_whitespace();
+                let start = self.pos;
+                if self.eat("]") {
+                    break;
+                }
+                self.subset_declaration(false)?;
+                doctype.add_raw(&self.input[start..self.pos]);
+            }
+            self.skip_whitespace();
+        }
+        self.expect(">")?;
+        Ok(Token::Doctype(doctype))
+    }
+
+    /// Skips a markup declaration, comment, processing instruction or
+    /// parameter entity reference in the internal subset. Entity declarations
+    /// and parameter entity references are only accepted if `entities` is set.
+    fn subset_declaration(&mut self, entities: bool) -> Result<(), XMLError> {
+        let rest = self.rest();
+        let start = self.pos;
+        if rest.starts_with("<!ENTITY") && !entities {
+            Err(self.error(start, "entity declarations are not supported"))
+        } else if rest.starts_with('%') && entities {
+            self.pos += 1;
+            self.name()?;
+            self.expect(";")
+        } else if rest.starts_with("<!--") {
+            self.comment().map(drop)
+        } else if rest.starts_with("<?") {
+            self.processing_instruction(false).map(drop)
+        } else if rest.starts_with("<!") {
+            self.markup_declaration(entities)
+        } else if rest.starts_with('%') {
+            Err(self.error(start, "parameter entity references are not supported"))
+        } else {
+            Err(self.error(start, "expected markup declaration or `]`"))
+        }
+    }
+
+    /// Skips an element, attribute list, notation or, if `entities` is set,
+    /// entity declaration.
+    fn markup_declaration(&mut self, entities: bool) -> Result<(), XMLError> {
+        self.pos += 2;
+        loop {
+            match self.rest().chars().next() {
+                Some('>') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some('"') | Some('\'') => {
+                    self.quoted()?;
+                }
+                Some('%') if entities => self.pos += 1,
+                Some('%') => {
+                    return Err(
+                        self.error(self.pos, "parameter entity references are not supported")
+                    )
+                }
+                Some(c) => self.pos += c.len_utf8(),
+                None => return Err(self.token_error("unterminated markup declaration")),
+            }
+        }
+    }
+
+    /// Decodes references in text or an attribute value, and normalizes line
+    /// endings, or whitespace in attribute values.
+    fn decode(&self, raw: &'a str, pos: usize, attribute: bool) -> Result<Cow<'a, str>, XMLError> {
+        self.check_chars(raw, pos)?;
+        let special: &[char] = if attribute {
+            &['&', '\r', '\n', '\t']
+        } else {
+            &['&', '\r']
+        };
+        if !raw.contains(special) {
+            return Ok(Cow::Borrowed(raw));
+        }
+        let mut result = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(i) = rest.find(special) {
+            result += &rest[..i];
+            let c = rest.as_bytes()[i] as char;
+            rest = &rest[i + 1..];
+            match c {
+                '&' => {
+                    let ref_pos = pos + (raw.len() - rest.len()) - 1;
+                    let end = match rest.find(';') {
+                        Some(end) => end,
+                        None => return Err(self.error(ref_pos, "unterminated reference")),
+                    };
+                    match decode_reference(&rest[..end]) {
+                        Some(c) => result.push(c),
+                        None => {
+                            let message = format!("undefined entity `{}`", &rest[..end]);
+                            return Err(self.error(ref_pos, message));
+                        }
+                    }
+                    rest = &rest[end + 1..];
+                }
+                '\r' => {
+                    if rest.starts_with('\n') {
+                        rest = &rest[1..];
+                    }
+                    result.push(if attribute { ' ' } else { '\n' });
+                }
+                _ => result.push(' '),
+            }
+        }
+        result += rest;
+        Ok(Cow::Owned(result))
+    }
+
+    /// Checks that `text`, found at `pos`, only contains characters allowed
+    /// in XML documents.
+    fn check_chars(&self, text: &str, pos: usize) -> Result<(), XMLError> {
+        match text.char_indices().find(|&(_, c)| !is_xml_char(c)) {
+            Some((i, c)) => {
+                let message = format!("invalid character U+{:04X}", c as u32);
+                Err(self.error(pos + i, message))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), XMLError> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(self.pos, format!("expected `{}`", s)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = self.rest();
+        let trimmed = rest.trim_start_matches(is_whitespace);
+        self.pos += rest.len() - trimmed.len();
+        trimmed.len() != rest.len()
+    }
+
+    fn name(&mut self) -> Result<&'a str, XMLError> {
+        let rest = self.rest();
+        match rest.chars().next() {
+            Some(c) if is_name_start_char(c) => {}
+            _ => return Err(self.error(self.pos, "expected a name")),
+        }
+        let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    fn quoted(&mut self) -> Result<&'a str, XMLError> {
+        let rest = self.rest();
+        let quote = match rest.chars().next() {
+            Some(quote @ '"') | Some(quote @ '\'') => quote,
+            _ => return Err(self.error(self.pos, "expected a quoted value")),
+        };
+        match rest[1..].find(quote) {
+            Some(end) => {
+                self.pos += end + 2;
+                Ok(&rest[1..end + 1])
+            }
+            None => Err(self.error(self.pos, "unterminated quoted value")),
+        }
+    }
+
+    /// Returns the text up to `delimiter`, moving past it.
+    fn until(&mut self, delimiter: &str, what: &str) -> Result<&'a str, XMLError> {
+        let rest = self.rest();
+        match rest.find(delimiter) {
+            Some(end) => {
+                self.pos += end + delimiter.len();
+                Ok(&rest[..end])
+            }
+            None => Err(self.token_error(format!("unterminated {}", what))),
+        }
+    }
+
+    fn error(&self, pos: usize, message: impl ToString) -> XMLError {
+        let before = &self.input[..pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        XMLError::Parse {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Returns whether `c` matches the XML `S` production.
+pub(crate) fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
+
+/// Returns whether `c` matches the XML 1.0 `Char` production.
+pub(crate) fn is_xml_char(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..
+    )
+}
+
+/// Decodes the content of a predefined entity or character reference.
+fn decode_reference(reference: &str) -> Option<char> {
+    let code = match reference {
+        "lt" => return Some('<'),
+        "gt" => return Some('>'),
+        "amp" => return Some('&'),
+        "apos" => return Some('\''),
+        "quot" => return Some('"'),
+        _ if reference.starts_with("#x") => u32::from_str_radix(&reference[2..], 16).ok()?,
+        _ if reference.starts_with('#') => reference[1..].parse().ok()?,
+        _ => return None,
+    };
+    std::char::from_u32(code).filter(|&c| is_xml_char(c))
+}
+
+fn normalize_newlines(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Decodes the bytes of an XML document, detecting the encoding from a byte
+/// order mark or the XML declaration.
+pub(crate) fn decode_input(bytes: &[u8]) -> Result<String, XMLError> {
+    let error = |message: &str| XMLError::Parse {
+        line: 1,
+        column: 1,
+        message: message.to_owned(),
+    };
+    let encoding = match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => Encoding::Utf8,
+        [0xFF, 0xFE, ..] | [b'<', 0, b'?', 0, ..] => Encoding::Utf16Le,
+        [0xFE, 0xFF, ..] | [0, b'<', 0, b'?', ..] => Encoding::Utf16Be,
+        _ => match declared_encoding(bytes) {
+            Some(label) => match Encoding::for_label(label) {
+                Some(Encoding::Utf16Le) | Some(Encoding::Utf16Be) => {
+                    return Err(error("UTF-16 input must start with a byte order mark"))
+                }
+                Some(encoding) => encoding,
+                None => return Err(error(&format!("unsupported encoding `{}`", label))),
+            },
+            None => Encoding::Utf8,
+        },
+    };
+    let bytes = match (encoding, bytes) {
+        (Encoding::Utf16Le, [0xFF, 0xFE, rest @ ..])
+        | (Encoding::Utf16Be, [0xFE, 0xFF, rest @ ..]) => rest,
+        _ => bytes,
+    };
+    encoding
+        .decode(bytes)
+        .ok_or_else(|| error(&format!("input is not valid {}", encoding.label())))
+}
+
+/// Returns the encoding label from the XML declaration at the start of an
+/// ASCII-compatible document.
+fn declared_encoding(bytes: &[u8]) -> Option<&str> {
+    if !bytes.starts_with(b"<?xml") {
+        return None;
+    }
+    let end = bytes.windows(2).position(|w| w == b"?>")?;
+    let declaration = std::str::from_utf8(&bytes[..end]).ok()?;
+    let rest = declaration[declaration.find("encoding")? + "encoding".len()..].trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let rest = &rest[1..];
+    Some(&rest[..rest.find(quote)?])
+}
+
+struct OpenElement {
+    element: XMLElement,
+    scope: usize,
+}
+
+/// Parses XML text into a document.
+pub(crate) fn parse_document(input: &str, options: &ParseOptions) -> Result<XMLDocument, XMLError> {
+    let mut reader = XMLReader::from_text(input);
+    let mut declaration = None;
+    let mut doctype = None;
+    let mut prolog = Vec::new();
+    let mut root = None;
+    let mut epilog = Vec::new();
+    let mut stack: Vec<OpenElement> = Vec::new();
+    let mut namespaces: Vec<(String, String)> = Vec::new();
+    while let Some(event) = reader.next() {
+        let node = match event? {
+            XMLEvent::Declaration {
+                version,
+                encoding,
+                standalone,
+            } => {
+                let mut decl = XMLDeclaration::new()
+                    .version(version)
+                    .standalone(standalone);
+                if encoding.is_none() {
+                    decl = decl.without_encoding();
+                }
+                declaration = Some(decl);
+                continue;
+            }
+            XMLEvent::Doctype(decl) => {
+                doctype = Some((prolog.len(), decl));
+                continue;
+            }
+            XMLEvent::StartElement { name, attributes } => {
+                if stack.len() >= options.max_depth {
+                    let message = format!("elements nested deeper than {}", options.max_depth);
+                    return Err(reader.token_error(message));
+                }
+                let scope = namespaces.len();
+                let element = start_element(&name, &attributes, &mut namespaces)
+                    .map_err(|message| reader.token_error(message))?;
+                stack.push(OpenElement { element, scope });
+                continue;
+            }
+            XMLEvent::EndElement { .. } => {
+                let mut open = stack.pop().expect("reader checks end tags");
+                namespaces.truncate(open.scope);
+                if !options.keep_whitespace && is_element_only(&open.element) {
+                    open.element
+                        .content
+                        .retain(|node| !matches!(node, XMLNode::Text(_)));
+                }
+                XMLNode::Element(open.element)
+            }
+            XMLEvent::Text(text) => XMLNode::Text(text.into_owned()),
+            XMLEvent::CData(text) => XMLNode::CData(text.into_owned()),
+            XMLEvent::Comment(text) => XMLNode::Comment(text.into_owned()),
+            XMLEvent::ProcessingInstruction { target, data } => XMLNode::ProcessingInstruction {
+                target: target.into_owned(),
+                data: data.into_owned(),
+            },
+        };
+        match (stack.last_mut(), node) {
+            (Some(open), node) => open.element.add_node(node),
+            (None, XMLNode::Element(element)) => root = Some(element),
+            (None, node) if root.is_some() => epilog.push(node),
+            (None, node) => prolog.push(node),
+        }
+    }
+    let mut document = XMLDocument::new(root.expect("reader checks for a root element"));
+    document.set_declaration(declaration);
+    let mut prolog = prolog.into_iter();
+    if let Some((position, doctype)) = doctype {
+        for node in prolog.by_ref().take(position) {
+            document.add_prolog(node)?;
+        }
+        document.set_doctype(Some(doctype));
+    }
+    for node in prolog {
+        document.add_prolog(node)?;
+    }
+    for node in epilog {
+        document.add_epilog(node)?;
+    }
+    Ok(document)
+}
+
+/// Returns whether an element contains no text other than whitespace.
+fn is_element_only(element: &XMLElement) -> bool {
+    element.content.iter().all(|node| match node {
+        XMLNode::Text(text) => text.chars().all(is_whitespace),
+        XMLNode::CData(_) => false,
+        _ => true,
+    })
+}
+
+/// Checks that `input` is a well-formed internal subset of a document type
+/// declaration, allowing entity declarations.
+pub(crate) fn check_internal_subset(input: &str) -> Result<(), XMLError> {
+    let mut tokenizer = Tokenizer::chunk(input, false);
+    tokenizer.check_chars(input, 0)?;
+    loop {
+        tokenizer.skip_whitespace();
+        if tokenizer.rest().is_empty() {
+            return Ok(());
+        }
+        tokenizer.subset_declaration(true)?;
+    }
+}
+
+/// Checks that `input` is well-formed element content.
+pub(crate) fn check_fragment(input: &str) -> Result<(), XMLError> {
+    let mut tokenizer = Tokenizer::chunk(input, false);
+    let mut stack = Vec::new();
+    while let Some(token) = tokenizer.next_token()? {
+        match token {
+            Token::Doctype(_) => {
+                return Err(tokenizer.token_error("misplaced document type declaration"))
+            }
+            Token::StartTag {
+                name, empty: false, ..
+            } => stack.push(name),
+            Token::EndTag { name } if stack.pop() != Some(name) => {
+                let message = format!("unexpected end tag `{}`", name);
+                return Err(tokenizer.token_error(message));
+            }
+            _ => {}
+        }
+    }
+    match stack.last() {
+        Some(open) => Err(tokenizer.end_error(format!("unclosed element `{}`", open))),
+        None => Ok(()),
+    }
+}
+
+/// Creates an element from a start tag, resolving namespace prefixes and
+/// adding the tag's namespace declarations to `namespaces`.
+fn start_element(
+    name: &str,
+    attributes: &[(Cow<str>, Cow<str>)],
+    namespaces: &mut Vec<(String, String)>,
+) -> Result<XMLElement, String> {
+    let mut declared = Vec::new();
+    for (attribute, value) in attributes {
+        let prefix = match split_name(attribute)? {
+            (None, "xmlns") => "",
+            (Some("xmlns"), prefix) if !value.is_empty() => prefix,
+            (Some("xmlns"), prefix) => return Err(format!("cannot undeclare prefix `{}`", prefix)),
+            _ => continue,
+        };
+        namespaces.push((prefix.to_owned(), value.to_string()));
+        declared.push((prefix, value));
+    }
+
+    let mut element = match split_name(name)? {
+        (Some(prefix), local) => XMLElement::new_ns(resolve(prefix, namespaces)?, local),
+        (None, local) => match namespaces
+            .iter()
+            .rev()
+            .find(|(prefix, _)| prefix.is_empty())
+        {
+            Some((_, uri)) if !uri.is_empty() => XMLElement::new_ns(uri, local),
+            _ => XMLElement::new(local),
+        },
+    };
+    for (prefix, uri) in declared {
+        if !uri.is_empty() {
+            element.set_namespace_prefix(uri, prefix);
+        }
+    }
+
+    let mut seen = Vec::new();
+    for (attribute, value) in attributes {
+        let expanded = match split_name(attribute)? {
+            (None, "xmlns") | (Some("xmlns"), _) => continue,
+            (Some(prefix), local) => (Some(resolve(prefix, namespaces)?), local),
+            (None, local) => (None, local),
+        };
+        if seen.contains(&expanded) {
+            return Err(format!("duplicate attribute `{}`", attribute));
+        }
+        match &expanded {
+            (Some(uri), local) => element.add_attribute_ns(uri, local, value),
+            (None, local) => element.add_attribute(local, value),
+        }
+        seen.push(expanded);
+    }
+    Ok(element)
+}
+
+fn split_name(name: &str) -> Result<(Option<&str>, &str), String> {
+    let mut parts = name.splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(local), None, None) => Ok((None, local)),
+        (Some(prefix), Some(local), None) if !prefix.is_empty() && !local.is_empty() => {
+            Ok((Some(prefix), local))
+        }
+        _ => Err(format!("invalid qualified name `{}`", name)),
+    }
+}
+
+fn resolve(prefix: &str, namespaces: &[(String, String)]) -> Result<String, String> {
+    if prefix == "xml" {
+        return Ok(XML_NAMESPACE.to_owned());
+    }
+    namespaces
+        .iter()
+        .rev()
+        .find(|(declared, _)| *declared == prefix)
+        .map(|(_, uri)| uri.clone())
+        .ok_or_else(|| format!("undeclared namespace prefix `{}`", prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use {ParseOptions, XMLDocument, XMLElement, XMLError};
+
+    fn parse_error(input: &str) -> (usize, usize, String) {
+        match input.parse::<XMLDocument>() {
+            Err(XMLError::Parse {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            other => panic!("expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE root [
+	<!ELEMENT root ANY>
+]>
+<!-- prolog -->
+<root xmlns="urn:a" xmlns:b="urn:b" b:id="1">
+	<b:child xml:lang="en">a &amp; &lt;b&gt; &#x263A;</b:child>
+	<p>Hello <b>world</b>!</p>
+	<script><![CDATA[x < y]]></script>
+	<?app data?>
+	<empty />
+</root>
+"#;
+        let document: XMLDocument = input.parse().unwrap();
+        assert_eq!(document.to_string(), input.replace("&#x263A;", "\u{263A}"));
+    }
+
+    #[test]
+    fn whitespace() {
+        let input = "<a>\r\n  <b>x\r\ny</b> <c/></a>";
+        let document: XMLDocument = input.parse().unwrap();
+        assert_eq!(format!("{:#}", document), "<a>\n  <b>x\ny</b> <c/></a>");
+        let document: XMLDocument = "<p><b>a</b> <i>b</i></p>".parse().unwrap();
+        assert_eq!(format!("{:#}", document), "<p><b>a</b> <i>b</i></p>");
+
+        let options = ParseOptions::new().keep_whitespace(false);
+        let element = XMLDocument::from_str_with(input, &options)
+            .unwrap()
+            .into_root();
+        assert_eq!(
+            element.to_compact_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?><a><b>x
+y</b><c/></a>"#
+        );
+        let document = XMLDocument::from_str_with("<p><b>a</b> <i>b</i>.</p>", &options).unwrap();
+        assert_eq!(format!("{:#}", document), "<p><b>a</b> <i>b</i>.</p>");
+        let document = XMLDocument::from_str_with("<p> <![CDATA[a]]> </p>", &options).unwrap();
+        assert_eq!(format!("{:#}", document), "<p> <![CDATA[a]]> </p>");
+    }
+
+    #[test]
+    fn unused_namespace_declarations() {
+        let input = r#"<a xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:t="urn:t" xsi:type="t:Foo"/>"#;
+        let document: XMLDocument = input.parse().unwrap();
+        assert_eq!(format!("{:#}", document), input);
+    }
+
+    #[test]
+    fn doctype_comments() {
+        let input = "<!DOCTYPE a [<!-- ]> -->]><a/>";
+        let document: XMLDocument = input.parse().unwrap();
+        assert_eq!(format!("{:#}", document), input);
+    }
+
+    #[test]
+    fn attribute_normalization() {
+        let element: XMLElement = "<a v='x\ty&#10;z\"'/>".parse().unwrap();
+        assert_eq!(
+            element.to_compact_string(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><a v=\"x y&#10;z&quot;\"/>"
+        );
+        let mut element = XMLElement::new("a");
+        element.add_attribute("v", "\t\r\n");
+        let parsed: XMLElement = element.to_string().parse().unwrap();
+        assert_eq!(parsed.attribute("v"), Some("\t\r\n"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse_error("<a>\n  <b></a>"),
+            (2, 6, "unexpected end tag `a`".to_owned())
+        );
+        assert_eq!(parse_error("<a>&foo;</a>").2, "undefined entity `foo`");
+        assert_eq!(parse_error("<a b='1' b='2'/>").2, "duplicate attribute `b`");
+        assert_eq!(parse_error("<x:a/>").2, "undeclared namespace prefix `x`");
+        assert_eq!(parse_error("<a/><b/>").2, "multiple root elements");
+        assert_eq!(parse_error("<a>").2, "unclosed element `a`");
+        assert_eq!(parse_error(" <?xml version='1.0'?><a/>").1, 2);
+        assert_eq!(
+            parse_error("<a>\n x\u{1}</a>"),
+            (2, 3, "invalid character U+0001".to_owned())
+        );
+        assert_eq!(
+            parse_error("<a b='\u{1}'/>"),
+            (1, 7, "invalid character U+0001".to_owned())
+        );
+        assert_eq!(parse_error("<a><!--\u{1}--></a>").1, 8);
+        assert_eq!(parse_error("<a><![CDATA[\u{FFFF}]]></a>").1, 13);
+        assert_eq!(parse_error("<?pi \u{1}?><a/>").1, 6);
+    }
+
+    #[test]
+    fn max_depth() {
+        let nested = |depth| "<a>".repeat(depth) + &"</a>".repeat(depth);
+        let document: XMLDocument = nested(256).parse().unwrap();
+        assert_eq!(format!("{:#}", document).matches("</a>").count(), 255);
+        assert_eq!(
+            parse_error(&nested(257)),
+            (1, 769, "elements nested deeper than 256".to_owned())
+        );
+        let options = ParseOptions::new().max_depth(2);
+        assert!(XMLDocument::from_str_with(&nested(2), &options).is_ok());
+        assert!(XMLDocument::from_str_with(&nested(3), &options).is_err());
+    }
+
+    #[test]
+    fn rejects_entity_expansion() {
+        let billion_laughs = r#"<?xml version="1.0"?>
+<!DOCTYPE lolz [
+ <!ENTITY lol "lol">
+ <!ENTITY lol1 "&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;">
+]>
+<lolz>&lol1;</lolz>"#;
+        assert_eq!(
+            parse_error(billion_laughs),
+            (3, 2, "entity declarations are not supported".to_owned())
+        );
+        let external =
+            r#"<!DOCTYPE a [<!ENTITY % ext SYSTEM "http://example.com/x.dtd"> %ext;]><a/>"#;
+        assert!(external.parse::<XMLDocument>().is_err());
+        assert_eq!(
+            parse_error(r#"<!DOCTYPE a SYSTEM "a.dtd"><a>&ext;</a>"#).2,
+            "undefined entity `ext`"
+        );
+    }
+
+    #[test]
+    fn from_reader_encodings() {
+        let latin1 = b"<?xml version='1.0' encoding='ISO-8859-1'?><caf\xE9>\xE9</caf\xE9>";
+        let element = XMLElement::from_reader(&latin1[..]).unwrap();
+        assert_eq!(
+            element.to_compact_string(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><caf\u{E9}>\u{E9}</caf\u{E9}>"
+        );
+        let utf16 = b"\xFF\xFE<\x00a\x00/\x00>\x00";
+        assert!(XMLElement::from_reader(&utf16[..]).is_ok());
+        assert!(XMLElement::from_reader(&b"<a>\xFF</a>"[..]).is_err());
+    }
+}
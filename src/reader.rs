@@ -0,0 +1,589 @@
+use parser::{is_whitespace, Token, Tokenizer};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use {Encoding, XMLDoctype, XMLError};
+
+/// An event read by [XMLReader].
+///
+/// Names are qualified names as written in the document. References in text
+/// and attribute values are decoded, so the strings are borrowed from the
+/// input where possible.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum XMLEvent<'a> {
+    /// The XML declaration.
+    Declaration {
+        version: Cow<'a, str>,
+        encoding: Option<Cow<'a, str>>,
+        standalone: Option<bool>,
+    },
+    /// The document type declaration.
+    Doctype(XMLDoctype),
+    /// A start tag, including namespace declarations among the attributes.
+    /// An empty element tag is read as a start tag and an end tag.
+    StartElement {
+        name: Cow<'a, str>,
+        attributes: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    },
+    /// An end tag.
+    EndElement { name: Cow<'a, str> },
+    /// Character data within an element.
+    Text(Cow<'a, str>),
+    /// The content of a CDATA section.
+    CData(Cow<'a, str>),
+    /// The text of a comment.
+    Comment(Cow<'a, str>),
+    /// A processing instruction.
+    ProcessingInstruction {
+        target: Cow<'a, str>,
+        data: Cow<'a, str>,
+    },
+}
+
+impl<'a> XMLEvent<'a> {
+    /// Converts the event into one that owns its strings.
+    pub fn into_owned(self) -> XMLEvent<'static> {
+        let owned = |s: Cow<str>| Cow::Owned(s.into_owned());
+        match self {
+            XMLEvent::Declaration {
+                version,
+                encoding,
+                standalone,
+            } => XMLEvent::Declaration {
+                version: owned(version),
+                encoding: encoding.map(owned),
+                standalone,
+            },
+            XMLEvent::Doctype(doctype) => XMLEvent::Doctype(doctype),
+            XMLEvent::StartElement { name, attributes } => XMLEvent::StartElement {
+                name: owned(name),
+                attributes: attributes
+                    .into_iter()
+                    .map(|(name, value)| (owned(name), owned(value)))
+                    .collect(),
+            },
+            XMLEvent::EndElement { name } => XMLEvent::EndElement { name: owned(name) },
+            XMLEvent::Text(text) => XMLEvent::Text(owned(text)),
+            XMLEvent::CData(text) => XMLEvent::CData(owned(text)),
+            XMLEvent::Comment(text) => XMLEvent::Comment(owned(text)),
+            XMLEvent::ProcessingInstruction { target, data } => XMLEvent::ProcessingInstruction {
+                target: owned(target),
+                data: owned(data),
+            },
+        }
+    }
+
+    /// Converts a token into an event, also returning whether it was an empty
+    /// element tag.
+    fn from_token(token: Token<'a>) -> (Self, bool) {
+        let event = match token {
+            Token::Declaration {
+                version,
+                encoding,
+                standalone,
+            } => XMLEvent::Declaration {
+                version: Cow::Borrowed(version),
+                encoding: encoding.map(Cow::Borrowed),
+                standalone,
+            },
+            Token::Doctype(doctype) => XMLEvent::Doctype(doctype),
+            Token::StartTag {
+                name,
+                attributes,
+                empty,
+            } => {
+                let attributes = attributes
+                    .into_iter()
+                    .map(|(name, value)| (Cow::Borrowed(name), value))
+                    .collect();
+                let name = Cow::Borrowed(name);
+                return (XMLEvent::StartElement { name, attributes }, empty);
+            }
+            Token::EndTag { name } => XMLEvent::EndElement {
+                name: Cow::Borrowed(name),
+            },
+            Token::Text(text) => XMLEvent::Text(text),
+            Token::CData(text) => XMLEvent::CData(text),
+            Token::Comment(text) => XMLEvent::Comment(text),
+            Token::ProcessingInstruction { target, data } => XMLEvent::ProcessingInstruction {
+                target: Cow::Borrowed(target),
+                data,
+            },
+        };
+        (event, false)
+    }
+}
+
+/// Reads an XML document as a stream of events, without building a tree.
+///
+/// The reader checks that the document is well-formed, with the same rules
+/// as [XMLDocument](::XMLDocument) parsing, and returns an error as the last
+/// event otherwise. Whitespace outside the root element is skipped, but all
+/// text within it is returned. Namespace prefixes are not resolved, so they
+/// are not checked to be declared, and attributes are only checked to be
+/// unique by their qualified names.
+///
+/// Events can be written with [XMLWriter::write_event](::XMLWriter::write_event),
+/// which reproduces the document when writing compact output.
+///
+/// # Example
+///
+/// ```rust
+/// # use simple_xml_builder::XMLError;
+/// # fn main() -> Result<(), XMLError> {
+/// use simple_xml_builder::{XMLEvent, XMLReader};
+///
+/// let reader = XMLReader::new(&b"<list><item>a &amp; b</item><item/></list>"[..]);
+/// let mut items = Vec::new();
+/// for event in reader {
+///     if let XMLEvent::Text(text) = event? {
+///         items.push(text);
+///     }
+/// }
+/// assert_eq!(items, ["a & b"]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct XMLReader<'a, R: BufRead = io::Empty> {
+    source: Source<'a, R>,
+    pending: Option<XMLEvent<'a>>,
+    stack: Vec<String>,
+    has_root: bool,
+    has_doctype: bool,
+    done: bool,
+}
+
+enum Source<'a, R> {
+    Text(Tokenizer<'a>),
+    Stream(Stream<R>),
+}
+
+impl<'a> XMLReader<'a> {
+    /// Creates a reader for a document in memory. Events borrow from `text`
+    /// where possible.
+    pub fn from_text(text: &'a str) -> Self {
+        Self::with_source(Source::Text(Tokenizer::new(text)))
+    }
+}
+
+impl<R: BufRead> XMLReader<'static, R> {
+    /// Creates a reader for a document read incrementally from `reader`, so
+    /// only the current event is held in memory. Events own their strings.
+    ///
+    /// The input may be encoded as UTF-8, ISO-8859-1 or Windows-1252, as
+    /// specified in the XML declaration. UTF-16 is only supported by
+    /// [XMLDocument::from_reader](::XMLDocument::from_reader).
+    pub fn new(reader: R) -> Self {
+        Self::with_source(Source::Stream(Stream::new(reader)))
+    }
+}
+
+impl<'a, R: BufRead> XMLReader<'a, R> {
+    fn with_source(source: Source<'a, R>) -> Self {
+        XMLReader {
+            source,
+            pending: None,
+            stack: Vec::new(),
+            has_root: false,
+            has_doctype: false,
+            done: false,
+        }
+    }
+
+    /// Returns an error located at the start of the last event.
+    pub(crate) fn token_error(&self, message: impl ToString) -> XMLError {
+        match &self.source {
+            Source::Text(tokenizer) => tokenizer.token_error(message),
+            Source::Stream(stream) => stream.error(stream.token_position, message),
+        }
+    }
+
+    fn end_error(&self, message: impl ToString) -> XMLError {
+        match &self.source {
+            Source::Text(tokenizer) => tokenizer.end_error(message),
+            Source::Stream(stream) => stream.error(stream.position, message),
+        }
+    }
+
+    fn next_event(&mut self) -> Result<Option<XMLEvent<'a>>, XMLError> {
+        if let Some(event) = self.pending.take() {
+            return Ok(Some(event));
+        }
+        loop {
+            let next = match &mut self.source {
+                Source::Text(tokenizer) => tokenizer.next_token()?.map(XMLEvent::from_token),
+                Source::Stream(stream) => stream.next()?,
+            };
+            let (event, empty) = match next {
+                Some(next) => next,
+                None => return self.end().map(|()| None),
+            };
+            match &event {
+                XMLEvent::Doctype(_) => {
+                    if self.has_doctype || self.has_root {
+                        return Err(self.token_error("misplaced document type declaration"));
+                    }
+                    self.has_doctype = true;
+                }
+                XMLEvent::StartElement { name, .. } => {
+                    if self.has_root && self.stack.is_empty() {
+                        return Err(self.token_error("multiple root elements"));
+                    }
+                    self.has_root = true;
+                    if empty {
+                        self.pending = Some(XMLEvent::EndElement { name: name.clone() });
+                    } else {
+                        self.stack.push(name.to_string());
+                    }
+                }
+                XMLEvent::EndElement { name } => match self.stack.pop() {
+                    Some(open) if open == *name => {}
+                    _ => {
+                        let message = format!("unexpected end tag `{}`", name);
+                        return Err(self.token_error(message));
+                    }
+                },
+                XMLEvent::Text(text) if self.stack.is_empty() => {
+                    if !text.chars().all(is_whitespace) {
+                        return Err(self.token_error("text outside the root element"));
+                    }
+                    continue;
+                }
+                XMLEvent::CData(_) if self.stack.is_empty() => {
+                    return Err(self.token_error("CDATA section outside the root element"));
+                }
+                _ => {}
+            }
+            return Ok(Some(event));
+        }
+    }
+
+    fn end(&self) -> Result<(), XMLError> {
+        if let Some(open) = self.stack.last() {
+            return Err(self.end_error(format!("unclosed element `{}`", open)));
+        }
+        if !self.has_root {
+            return Err(self.end_error("missing root element"));
+        }
+        Ok(())
+    }
+}
+
+impl<'a, R: BufRead> Iterator for XMLReader<'a, R> {
+    type Item = Result<XMLEvent<'a>, XMLError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_event();
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
+}
+
+/// Splits input from a reader into chunks containing a single token, which
+/// are decoded and tokenized separately.
+struct Stream<R> {
+    reader: R,
+    encoding: Encoding,
+    started: bool,
+    chunk: Vec<u8>,
+    events: VecDeque<(XMLEvent<'static>, bool)>,
+    /// The line and column of the end of the last chunk.
+    position: (usize, usize),
+    /// The line and column of the start of the last chunk.
+    token_position: (usize, usize),
+}
+
+impl<R: BufRead> Stream<R> {
+    fn new(reader: R) -> Self {
+        Stream {
+            reader,
+            encoding: Encoding::Utf8,
+            started: false,
+            chunk: Vec::new(),
+            events: VecDeque::new(),
+            position: (1, 1),
+            token_position: (1, 1),
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<(XMLEvent<'static>, bool)>, XMLError> {
+        while self.events.is_empty() {
+            let at_start = !self.started;
+            if !self.read_chunk()? {
+                return Ok(None);
+            }
+            self.token_position = self.position;
+            let text = match self.encoding.decode(&self.chunk) {
+                Some(text) => text,
+                None => {
+                    let message = format!("input is not valid {}", self.encoding.label());
+                    return Err(self.error(self.token_position, message));
+                }
+            };
+            let mut tokenizer = Tokenizer::chunk(&text, at_start);
+            loop {
+                let token = match tokenizer.next_token() {
+                    Ok(Some(token)) => token,
+                    Ok(None) => break,
+                    Err(error) => return Err(self.relocate(error)),
+                };
+                if let Token::Declaration {
+                    encoding: Some(label),
+                    ..
+                } = token
+                {
+                    self.encoding = match Encoding::for_label(label) {
+                        Some(Encoding::Utf16Le) | Some(Encoding::Utf16Be) | None => {
+                            let message = format!("unsupported encoding `{}`", label);
+                            return Err(self.error(self.token_position, message));
+                        }
+                        Some(encoding) => encoding,
+                    };
+                }
+                let (event, empty) = XMLEvent::from_token(token);
+                self.events.push_back((event.into_owned(), empty));
+            }
+            for c in text.chars() {
+                self.position = match c {
+                    '\n' => (self.position.0 + 1, 1),
+                    _ => (self.position.0, self.position.1 + 1),
+                };
+            }
+        }
+        Ok(self.events.pop_front())
+    }
+
+    /// Reads the bytes of the next token into `chunk`. Returns `false` at the
+    /// end of the input.
+    fn read_chunk(&mut self) -> Result<bool, XMLError> {
+        self.chunk.clear();
+        if !self.started {
+            self.started = true;
+            let start = self.reader.fill_buf()?;
+            if start.starts_with(&[0xEF, 0xBB, 0xBF]) {
+                self.reader.consume(3);
+            } else if start.starts_with(&[0xFF, 0xFE]) || start.starts_with(&[0xFE, 0xFF]) {
+                return Err(self.error((1, 1), "unsupported encoding `UTF-16`"));
+            }
+        }
+        match self.peek()? {
+            None => return Ok(false),
+            Some(b'<') => {}
+            Some(_) => {
+                self.read_text()?;
+                return Ok(true);
+            }
+        }
+        self.bump()?;
+        match self.bump()? {
+            Some(b'?') => self.read_until(2, b"?>")?,
+            Some(b'!') => match self.bump()? {
+                Some(b'-') => self.read_until(4, b"-->")?,
+                Some(b'[') => self.read_until(9, b"]]>")?,
+                _ => self.read_tag(true)?,
+            },
+            _ => self.read_tag(false)?,
+        }
+        Ok(true)
+    }
+
+    /// Reads text up to the next `<`.
+    fn read_text(&mut self) -> io::Result<()> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(());
+            }
+            let (len, done) = match buf.iter().position(|&b| b == b'<') {
+                Some(i) => (i, true),
+                None => (buf.len(), false),
+            };
+            self.chunk.extend_from_slice(&buf[..len]);
+            self.reader.consume(len);
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reads up to and including `delimiter`, which must start at or after
+    /// `from` in the chunk.
+    fn read_until(&mut self, from: usize, delimiter: &[u8]) -> io::Result<()> {
+        while self.chunk.len() < from + delimiter.len() || !self.chunk.ends_with(delimiter) {
+            if self.bump()?.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads up to the `>` ending a tag or document type declaration, skipping
+    /// quoted values and, in a document type declaration, the internal subset.
+    fn read_tag(&mut self, doctype: bool) -> io::Result<()> {
+        let mut quote = None;
+        let mut in_subset = false;
+        while let Some(b) = self.bump()? {
+            match (quote, b) {
+                (Some(q), _) if b == q => quote = None,
+                (Some(_), _) => {}
+                (None, b'"') | (None, b'\'') => quote = Some(b),
+                (None, b'[') if doctype => in_subset = true,
+                (None, b']') if doctype => in_subset = false,
+                (None, b'>') if !in_subset => break,
+                (None, b'-') if in_subset && self.chunk.ends_with(b"<!--") => {
+                    self.read_until(self.chunk.len(), b"-->")?
+                }
+                (None, b'?') if in_subset && self.chunk.ends_with(b"<?") => {
+                    self.read_until(self.chunk.len(), b"?>")?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().cloned())
+    }
+
+    /// Moves the next byte into the chunk, returning it.
+    fn bump(&mut self) -> io::Result<Option<u8>> {
+        let next = self.peek()?;
+        if let Some(b) = next {
+            self.reader.consume(1);
+            self.chunk.push(b);
+        }
+        Ok(next)
+    }
+
+    /// Converts an error located within the last chunk to one located in the
+    /// whole input.
+    fn relocate(&self, error: XMLError) -> XMLError {
+        match error {
+            XMLError::Parse {
+                line: 1,
+                column,
+                message,
+            } => self.error(
+                (self.token_position.0, self.token_position.1 + column - 1),
+                message,
+            ),
+            XMLError::Parse {
+                line,
+                column,
+                message,
+            } => self.error((self.token_position.0 + line - 1, column), message),
+            error => error,
+        }
+    }
+
+    fn error(&self, (line, column): (usize, usize), message: impl ToString) -> XMLError {
+        XMLError::Parse {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::io::BufReader;
+    use {WriteOptions, XMLError, XMLEvent, XMLReader, XMLWriter};
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE root [<!ATTLIST root a CDATA "x>y"><!-- ] > -->]>
+<!-- prolog -->
+<root xmlns:b="urn:b" b:id="1 &gt; 0">
+	<b:child>a &amp; &lt;b&gt; &#x263A;</b:child>
+	<script><![CDATA[x < y]]></script>
+	<?app data?>
+	<empty/>
+</root>"#;
+
+    fn rewrite<'a>(events: impl Iterator<Item = Result<XMLEvent<'a>, XMLError>>) -> String {
+        let mut writer = XMLWriter::with_options(Vec::new(), WriteOptions::compact());
+        for event in events {
+            writer.write_event(&event.unwrap()).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn events() {
+        let events: Vec<_> = XMLReader::from_text("<a x='&lt;'>b<c/></a>")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            [
+                XMLEvent::StartElement {
+                    name: Cow::Borrowed("a"),
+                    attributes: vec![(Cow::Borrowed("x"), Cow::Owned("<".to_owned()))],
+                },
+                XMLEvent::Text(Cow::Borrowed("b")),
+                XMLEvent::StartElement {
+                    name: Cow::Borrowed("c"),
+                    attributes: Vec::new(),
+                },
+                XMLEvent::EndElement {
+                    name: Cow::Borrowed("c")
+                },
+                XMLEvent::EndElement {
+                    name: Cow::Borrowed("a")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let expected = DOCUMENT
+            .replacen(">\n<", "><", 3)
+            .replace("&#x263A;", "\u{263A}");
+        assert_eq!(rewrite(XMLReader::from_text(DOCUMENT)), expected);
+        let reader = XMLReader::new(BufReader::with_capacity(4, DOCUMENT.as_bytes()));
+        assert_eq!(rewrite(reader), expected);
+    }
+
+    #[test]
+    fn stream_errors() {
+        let error = |input: &[u8]| match XMLReader::new(input).last() {
+            Some(Err(XMLError::Parse {
+                line,
+                column,
+                message,
+            })) => (line, column, message),
+            other => panic!("expected parse error, got {:?}", other),
+        };
+        assert_eq!(
+            error(b"<a>\n  <b x='1'>&foo;</b></a>"),
+            (2, 12, "undefined entity `foo`".to_owned())
+        );
+        assert_eq!(
+            error(b"<a>\n  <b></a>"),
+            (2, 6, "unexpected end tag `a`".to_owned())
+        );
+        assert_eq!(error(b"<a>").2, "unclosed element `a`");
+        assert_eq!(
+            error(b"<a b='1' b='2'/>"),
+            (1, 10, "duplicate attribute `b`".to_owned())
+        );
+        assert_eq!(error(b"<a/> <?xml version='1.0'?>").1, 6);
+        assert!(XMLReader::new(&b"\xFF\xFE<\x00a\x00/\x00>\x00"[..]).any(|event| event.is_err()));
+        let latin1 = b"<?xml version='1.0' encoding='ISO-8859-1'?><a>\xE9</a>";
+        let texts: Vec<_> = XMLReader::new(&latin1[..])
+            .filter_map(|event| match event.unwrap() {
+                XMLEvent::Text(text) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, ["\u{E9}"]);
+    }
+}
@@ -0,0 +1,775 @@
+use encoding::Encoder;
+use namespace::Namespaces;
+use std::io::Write;
+use {
+    check_comment, check_processing_instruction, EmptyElement, Escaping, WriteOptions, XMLDoctype,
+    XMLElement, XMLError, XMLEvent, XMLNode,
+};
+
+/// Writes an XML document as a stream of events, without building a tree.
+///
+/// Output is escaped and indented the same way as
+/// [XMLElement::write](XMLElement::write), or as configured with
+/// [with_options](XMLWriter::with_options). Elements containing text are kept
+/// on a single line from the point the text is written.
+///
+/// # Example
+///
+/// ```rust
+/// # use simple_xml_builder::XMLError;
+/// # fn main() -> Result<(), XMLError> {
+/// use simple_xml_builder::{XMLElement, XMLWriter};
+///
+/// let mut writer = XMLWriter::new(Vec::new());
+/// writer.start_element("report")?;
+/// writer.attribute("rows", 2)?;
+/// for i in 0..2 {
+///     writer.start_element("row")?;
+///     writer.text(i)?;
+///     writer.end_element("row")?;
+/// }
+/// writer.write_element(&XMLElement::new("footer"))?;
+/// writer.end_element("report")?;
+/// let output = writer.finish()?;
+/// # assert_eq!(
+/// #     String::from_utf8(output).unwrap(),
+/// #     "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<report rows=\"2\">\n\t<row>0</row>\n\t<row>1</row>\n\t<footer />\n</report>\n"
+/// # );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct XMLWriter<W: Write> {
+    writer: Encoder<W>,
+    options: WriteOptions,
+    stack: Vec<OpenElement>,
+    state: State,
+    has_doctype: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum State {
+    /// Nothing has been written yet.
+    Start,
+    /// Before the root element, after the declaration or other nodes.
+    Prolog,
+    /// A start tag has been written but not closed, so attributes may follow.
+    StartTag,
+    /// Inside an element, after its start tag.
+    Content,
+    /// The root element has been closed.
+    Epilog,
+}
+
+#[derive(Debug)]
+struct OpenElement {
+    name: String,
+    has_content: bool,
+    has_text: bool,
+    inline: bool,
+}
+
+impl<W: Write> XMLWriter<W> {
+    /// Creates a new writer outputting a UTF-8 XML document to `writer`.
+    ///
+    /// The XML declaration, if any, is written along with the root element.
+    pub fn new(writer: W) -> Self {
+        Self::with_options(writer, WriteOptions::default())
+    }
+
+    /// Creates a new writer outputting an XML document to `writer`, formatted
+    /// and encoded according to `options`.
+    pub fn with_options(writer: W, options: WriteOptions) -> Self {
+        XMLWriter {
+            writer: Encoder::new(
+                writer,
+                options.output_encoding(),
+                options.is_ascii_only(),
+                options.invalid_chars_policy(),
+            ),
+            options,
+            stack: Vec::new(),
+            state: State::Start,
+            has_doctype: false,
+        }
+    }
+
+    /// Starts a new element, as a child of the current element or as the root
+    /// element.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::ContentOutsideRoot] if the root element has already
+    /// been closed, or [XMLError::Io] for errors from the underlying writer.
+    pub fn start_element(&mut self, name: impl ToString) -> Result<(), XMLError> {
+        self.start(name.to_string(), false)
+    }
+
+    /// Adds an attribute to the element that was just started. The value is
+    /// escaped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::AttributeAfterContent] if content has already been
+    /// written to the current element or no element is started, or
+    /// [XMLError::Io] for errors from the underlying writer.
+    pub fn attribute(&mut self, name: impl ToString, value: impl ToString) -> Result<(), XMLError> {
+        self.write_attribute(&name.to_string(), &value.to_string())
+    }
+
+    /// Writes text to the current element. The text is escaped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::ContentOutsideRoot] if no element is open, or
+    /// [XMLError::Io] for errors from the underlying writer.
+    pub fn text(&mut self, text: impl ToString) -> Result<(), XMLError> {
+        self.write_text(&text.to_string())
+    }
+
+    /// Writes a CDATA section to the current element. The text is not escaped,
+    /// and is split into multiple sections wherever it contains `]]>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::ContentOutsideRoot] if no element is open, or
+    /// [XMLError::Io] for errors from the underlying writer.
+    pub fn cdata(&mut self, text: impl ToString) -> Result<(), XMLError> {
+        self.start_text()?;
+        self.writer.markup("<![CDATA[")?;
+        self.writer
+            .cdata(&text.to_string().replace("]]>", "]]]]><![CDATA[>"))?;
+        self.writer.markup("]]>")
+    }
+
+    /// Writes a comment, either within the current element or before or after
+    /// the root element. The text is written as is.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidComment] if the text contains `--`, ends with
+    /// `-` or contains characters not allowed in XML, or [XMLError::Io] for errors from the underlying writer.
+    pub fn comment(&mut self, text: impl ToString) -> Result<(), XMLError> {
+        let text = text.to_string();
+        check_comment(&text)?;
+        self.start_markup()?;
+        self.writer.markup("<!--")?;
+        self.writer.markup(&text)?;
+        self.writer.markup("-->")
+    }
+
+    /// Writes a document type declaration. It must be written before the root
+    /// element, and at most once.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::MisplacedDoctype] if the root element was already
+    /// started or a document type declaration was already written,
+    /// [XMLError::InvalidDoctype] if the declaration is not well-formed, or
+    /// [XMLError::Io] for errors from the underlying writer.
+    pub fn doctype(&mut self, doctype: &XMLDoctype) -> Result<(), XMLError> {
+        let in_prolog = self.state == State::Start || self.state == State::Prolog;
+        if !in_prolog || self.has_doctype {
+            return Err(XMLError::MisplacedDoctype);
+        }
+        doctype.check()?;
+        self.has_doctype = true;
+        self.start_markup()?;
+        self.writer.markup(&doctype.header())?;
+        let mut subset = doctype.internal_subset().peekable();
+        if subset.peek().is_some() {
+            self.writer.markup(" [")?;
+            for decl in subset {
+                self.newline(1)?;
+                self.writer.markup(&decl)?;
+            }
+            self.newline(0)?;
+            self.writer.markup("]")?;
+        }
+        self.writer.markup(">")
+    }
+
+    /// Writes a processing instruction, either within the current element or
+    /// before or after the root element. The data is written as is.
+    ///
+    /// ```rust
+    /// # use simple_xml_builder::XMLError;
+    /// # fn main() -> Result<(), XMLError> {
+    /// use simple_xml_builder::{XMLElement, XMLWriter};
+    ///
+    /// let mut writer = XMLWriter::new(Vec::new());
+    /// writer.processing_instruction("xml-stylesheet", r#"type="text/xsl" href="style.xsl""#)?;
+    /// writer.write_element(&XMLElement::new("root"))?;
+    /// writer.finish()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::InvalidProcessingInstruction] if the target is not a
+    /// valid name, is `xml` in any case, or the data contains `?>` or
+    /// characters not allowed in XML, or [XMLError::Io] for errors from the underlying writer.
+    pub fn processing_instruction(
+        &mut self,
+        target: impl ToString,
+        data: impl ToString,
+    ) -> Result<(), XMLError> {
+        let target = target.to_string();
+        let data = data.to_string();
+        check_processing_instruction(&target, &data)?;
+        self.start_markup()?;
+        self.writer.markup("<?")?;
+        self.writer.markup(&target)?;
+        if !data.is_empty() {
+            self.writer.markup(" ")?;
+            self.writer.markup(&data)?;
+        }
+        self.writer.markup("?>")
+    }
+
+    /// Writes markup to the current element exactly as given. Like text, this
+    /// keeps the element on a single line. The markup is not checked, so
+    /// malformed markup produces a malformed document.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::ContentOutsideRoot] if no element is open,
+    /// [XMLError::Unencodable] if the markup cannot be represented in the
+    /// output encoding, or [XMLError::Io] for errors from the underlying
+    /// writer.
+    pub fn raw_unchecked(&mut self, xml: &str) -> Result<(), XMLError> {
+        self.start_text()?;
+        self.writer.markup(xml)
+    }
+
+    /// Ends the current element, which must have the given name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::MismatchedEndTag] if the name does not match the
+    /// current element or no element is open, or [XMLError::Io] for errors
+    /// from the underlying writer.
+    pub fn end_element(&mut self, name: impl AsRef<str>) -> Result<(), XMLError> {
+        let name = name.as_ref();
+        match self.stack.last() {
+            Some(open) if open.name == name => self.end(),
+            open => Err(XMLError::MismatchedEndTag {
+                expected: open.map(|open| open.name.clone()),
+                found: name.to_owned(),
+            }),
+        }
+    }
+
+    /// Writes an element and all of its content, as a child of the current
+    /// element or as the root element.
+    ///
+    /// Namespaces used by the element are declared on it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::ContentOutsideRoot] if the root element has already
+    /// been closed, or [XMLError::Io] for errors from the underlying writer.
+    pub fn write_element(&mut self, element: &XMLElement) -> Result<(), XMLError> {
+        let namespaces = Namespaces::new(element);
+        self.write_tree(element, &namespaces, true, false)
+    }
+
+    /// Writes a node, as by [write_element](XMLWriter::write_element),
+    /// [text](XMLWriter::text), [cdata](XMLWriter::cdata),
+    /// [comment](XMLWriter::comment),
+    /// [processing_instruction](XMLWriter::processing_instruction) or
+    /// [raw_unchecked](XMLWriter::raw_unchecked). Text nodes are escaped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the corresponding method.
+    pub fn write_node(&mut self, node: &XMLNode) -> Result<(), XMLError> {
+        match node {
+            XMLNode::Element(element) => self.write_element(element),
+            XMLNode::Text(text) => self.text(text),
+            XMLNode::CData(text) => self.cdata(text),
+            XMLNode::Comment(text) => self.comment(text),
+            XMLNode::ProcessingInstruction { target, data } => {
+                self.processing_instruction(target, data)
+            }
+            XMLNode::Raw(xml) => self.raw_unchecked(xml),
+        }
+    }
+
+    /// Writes an event from an [XMLReader](::XMLReader), so that documents can
+    /// be transformed while reading them. The XML declaration is written
+    /// according to the writer's options, so declaration events are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the corresponding method.
+    pub fn write_event(&mut self, event: &XMLEvent) -> Result<(), XMLError> {
+        match event {
+            XMLEvent::Declaration { .. } => Ok(()),
+            XMLEvent::Doctype(doctype) => self.doctype(doctype),
+            XMLEvent::StartElement { name, attributes } => {
+                self.start_element(name)?;
+                for (name, value) in attributes {
+                    self.attribute(name, value)?;
+                }
+                Ok(())
+            }
+            XMLEvent::EndElement { name } => self.end_element(name),
+            XMLEvent::Text(text) => self.text(text),
+            XMLEvent::CData(text) => self.cdata(text),
+            XMLEvent::Comment(text) => self.comment(text),
+            XMLEvent::ProcessingInstruction { target, data } => {
+                self.processing_instruction(target, data)
+            }
+        }
+    }
+
+    /// Finishes the document, returning the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [XMLError::IncompleteDocument] if no root element was written or
+    /// elements are still open, or [XMLError::Io] for errors from the
+    /// underlying writer.
+    pub fn finish(mut self) -> Result<W, XMLError> {
+        if self.state != State::Epilog {
+            return Err(XMLError::IncompleteDocument);
+        }
+        if self.options.has_trailing_newline() {
+            self.writer.markup(self.options.newline_str())?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer.into_inner())
+    }
+
+    fn write_tree(
+        &mut self,
+        element: &XMLElement,
+        namespaces: &Namespaces,
+        root: bool,
+        in_default: bool,
+    ) -> Result<(), XMLError> {
+        let inline = element
+            .content
+            .iter()
+            .any(|node| matches!(node, XMLNode::Text(_) | XMLNode::CData(_) | XMLNode::Raw(_)));
+        self.start(namespaces.element_name(&element.name), inline)?;
+        let mut in_default = in_default;
+        match (&element.name.namespace, namespaces.default()) {
+            (None, _) if in_default => {
+                self.write_attribute("xmlns", "")?;
+                in_default = false;
+            }
+            (Some(uri), Some(default)) if uri == default && !in_default => {
+                self.write_attribute("xmlns", uri)?;
+                in_default = true;
+            }
+            _ => {}
+        }
+        if root {
+            for (prefix, uri) in namespaces.declarations() {
+                self.write_attribute(&format!("xmlns:{}", prefix), uri)?;
+            }
+        }
+        for (name, value) in &element.attributes {
+            self.write_attribute(&namespaces.attribute_name(name), value)?;
+        }
+        for node in &element.content {
+            match node {
+                XMLNode::Element(child) => self.write_tree(child, namespaces, false, in_default)?,
+                XMLNode::Text(text) => self.write_text(text)?,
+                XMLNode::CData(text) => self.cdata(text)?,
+                XMLNode::Comment(text) => self.comment(text)?,
+                XMLNode::ProcessingInstruction { target, data } => {
+                    self.processing_instruction(target, data)?
+                }
+                XMLNode::Raw(xml) => self.raw_unchecked(xml)?,
+            }
+        }
+        self.end()
+    }
+
+    /// Starts an element. If `inline` is set, no whitespace is added to the
+    /// element's content.
+    fn start(&mut self, name: String, inline: bool) -> Result<(), XMLError> {
+        if self.state == State::Epilog {
+            return Err(XMLError::ContentOutsideRoot);
+        }
+        let parent_inline = self.start_markup()?;
+        self.writer.markup("<")?;
+        self.writer.markup(&name)?;
+        self.stack.push(OpenElement {
+            name,
+            has_content: false,
+            has_text: false,
+            inline: inline || parent_inline,
+        });
+        self.state = State::StartTag;
+        Ok(())
+    }
+
+    /// Prepares for writing an element, comment or other markup node, writing
+    /// the declaration and starting a new line as needed. Returns whether the
+    /// node is within inline content.
+    fn start_markup(&mut self) -> Result<bool, XMLError> {
+        if self.state == State::Start {
+            if let Some(declaration) = self.options.xml_declaration() {
+                let declaration = declaration.to_xml(self.options.output_encoding());
+                self.writer.markup(&declaration)?;
+                self.state = State::Prolog;
+            }
+        }
+        match self.state {
+            State::Start | State::Content => {}
+            State::Prolog | State::Epilog => self.newline(0)?,
+            State::StartTag => self.writer.markup(">")?,
+        }
+        self.state = match self.state {
+            State::Start => State::Prolog,
+            State::StartTag => State::Content,
+            state => state,
+        };
+        let level = self.stack.len();
+        let mut parent_inline = false;
+        if let Some(parent) = self.stack.last_mut() {
+            parent.has_content = true;
+            parent_inline = parent.inline || parent.has_text;
+        }
+        if level > 0 && !parent_inline {
+            self.newline(level)?;
+        }
+        Ok(parent_inline)
+    }
+
+    fn write_attribute(&mut self, name: &str, value: &str) -> Result<(), XMLError> {
+        if self.state != State::StartTag {
+            return Err(XMLError::AttributeAfterContent {
+                attribute: name.to_owned(),
+            });
+        }
+        self.writer.markup(" ")?;
+        self.writer.markup(name)?;
+        let quote = self.options.quote_for(value);
+        self.writer.markup(&format!("={}", quote))?;
+        let escaped = escape_attribute(value, self.options.escaping_profile(), quote);
+        self.writer.content(&escaped)?;
+        self.writer.markup(&quote.to_string())
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), XMLError> {
+        self.start_text()?;
+        self.writer
+            .content(&escape_text(text, self.options.escaping_profile()))
+    }
+
+    /// Prepares for writing text or CDATA to the current element.
+    fn start_text(&mut self) -> Result<(), XMLError> {
+        match self.state {
+            State::StartTag => self.writer.markup(">")?,
+            State::Content => {}
+            State::Start | State::Prolog | State::Epilog => {
+                return Err(XMLError::ContentOutsideRoot)
+            }
+        }
+        self.state = State::Content;
+        let open = self
+            .stack
+            .last_mut()
+            .expect("element open in content state");
+        open.has_content = true;
+        open.has_text = true;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), XMLError> {
+        let open = self.stack.pop().expect("element open when ending element");
+        if self.state == State::StartTag {
+            match self.options.empty_element_style() {
+                EmptyElement::SelfClosing => self.writer.markup("/>")?,
+                EmptyElement::SelfClosingSpace => self.writer.markup(" />")?,
+                EmptyElement::Expanded => self.writer.markup(&format!("></{}>", open.name))?,
+            }
+        } else {
+            if open.has_content && !open.has_text && !open.inline {
+                self.newline(self.stack.len())?;
+            }
+            self.writer.markup(&format!("</{}>", open.name))?;
+        }
+        self.state = if self.stack.is_empty() {
+            State::Epilog
+        } else {
+            State::Content
+        };
+        Ok(())
+    }
+
+    /// Starts a new line indented to `level`, unless writing compact output.
+    fn newline(&mut self, level: usize) -> Result<(), XMLError> {
+        if !self.options.is_pretty() {
+            return Ok(());
+        }
+        let indent = self.options.indent_str(level);
+        self.writer.markup(self.options.newline_str())?;
+        self.writer.markup(&indent)
+    }
+}
+
+fn escape_str(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_text(input: &str, escaping: Escaping) -> String {
+    match escaping {
+        Escaping::Full => escape_str(input),
+        Escaping::Minimal => input
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace("]]>", "]]&gt;"),
+    }
+}
+
+/// Escapes an attribute value written between `quote` characters. Whitespace
+/// other than spaces is written as character references, which are not
+/// affected by attribute value normalization when the document is parsed.
+fn escape_attribute(input: &str, escaping: Escaping, quote: char) -> String {
+    let escaped = match (escaping, quote) {
+        (Escaping::Full, _) => escape_str(input),
+        (Escaping::Minimal, '"') => input
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('"', "&quot;"),
+        (Escaping::Minimal, _) => input
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('\'', "&apos;"),
+    };
+    escaped
+        .replace('\t', "&#9;")
+        .replace('\n', "&#10;")
+        .replace('\r', "&#13;")
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        AttributeQuote, EmptyElement, Escaping, Indent, LineEnding, WriteOptions, XMLDoctype,
+        XMLElement, XMLError, XMLWriter,
+    };
+
+    #[test]
+    fn stream_matches_tree() {
+        let mut writer = XMLWriter::new(Vec::new());
+        writer.start_element("root").unwrap();
+        writer.start_element("p").unwrap();
+        writer.text("a & b ").unwrap();
+        writer.start_element("b").unwrap();
+        writer.start_element("br").unwrap();
+        writer.end_element("br").unwrap();
+        writer.end_element("b").unwrap();
+        writer.end_element("p").unwrap();
+        writer.start_element("list").unwrap();
+        writer.attribute("kind", "\"quoted\"").unwrap();
+        writer.start_element("item").unwrap();
+        writer.end_element("item").unwrap();
+        writer.end_element("list").unwrap();
+        writer.end_element("root").unwrap();
+        let output = writer.finish().unwrap();
+
+        let mut root = XMLElement::new("root");
+        let mut p = XMLElement::new("p");
+        p.add_text("a & b ");
+        let mut b = XMLElement::new("b");
+        b.add_child(XMLElement::new("br"));
+        p.add_node(b);
+        root.add_child(p);
+        let mut list = XMLElement::new("list");
+        list.add_attribute("kind", "\"quoted\"");
+        list.add_child(XMLElement::new("item"));
+        root.add_child(list);
+
+        assert_eq!(String::from_utf8(output).unwrap(), root.to_string());
+    }
+
+    #[test]
+    fn misuse() {
+        let mut writer = XMLWriter::new(Vec::new());
+        assert!(matches!(
+            writer.text("x"),
+            Err(XMLError::ContentOutsideRoot)
+        ));
+        writer.start_element("a").unwrap();
+        writer.text("x").unwrap();
+        assert!(matches!(
+            writer.attribute("k", "v"),
+            Err(XMLError::AttributeAfterContent { .. })
+        ));
+        assert!(matches!(
+            writer.end_element("b"),
+            Err(XMLError::MismatchedEndTag { .. })
+        ));
+        writer.end_element("a").unwrap();
+        assert!(matches!(
+            writer.start_element("a"),
+            Err(XMLError::ContentOutsideRoot)
+        ));
+        assert!(writer.finish().is_ok());
+
+        let mut writer = XMLWriter::new(Vec::new());
+        writer.start_element("a").unwrap();
+        assert!(matches!(writer.finish(), Err(XMLError::IncompleteDocument)));
+    }
+
+    #[test]
+    fn formatting_options() {
+        let options = WriteOptions::new()
+            .indent(Indent::Spaces(2))
+            .line_ending(LineEnding::CrLf)
+            .empty_element(EmptyElement::Expanded)
+            .trailing_newline(false);
+        let mut writer = XMLWriter::with_options(Vec::new(), options);
+        writer.start_element("a").unwrap();
+        writer.start_element("b").unwrap();
+        writer.start_element("c").unwrap();
+        writer.end_element("c").unwrap();
+        writer.end_element("b").unwrap();
+        writer.end_element("a").unwrap();
+        let output = writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\r\n<a>\r\n  <b>\r\n    <c></c>\r\n  </b>\r\n</a>"
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        let write = |escaping| {
+            let options = WriteOptions::compact().declaration(None).escaping(escaping);
+            let mut writer = XMLWriter::with_options(Vec::new(), options);
+            writer.start_element("a").unwrap();
+            writer.attribute("v", "<'&\">\n").unwrap();
+            writer.text("<'&\"> ]]>").unwrap();
+            writer.end_element("a").unwrap();
+            String::from_utf8(writer.finish().unwrap()).unwrap()
+        };
+        assert_eq!(
+            write(Escaping::Minimal),
+            "<a v=\"&lt;'&amp;&quot;>&#10;\">&lt;'&amp;\"> ]]&gt;</a>"
+        );
+        assert_eq!(
+            write(Escaping::Full),
+            "<a v=\"&lt;&apos;&amp;&quot;&gt;&#10;\">&lt;&apos;&amp;&quot;&gt; ]]&gt;</a>"
+        );
+    }
+
+    #[test]
+    fn attribute_quotes() {
+        let write = |quote| {
+            let options = WriteOptions::compact()
+                .declaration(None)
+                .escaping(Escaping::Minimal)
+                .attribute_quote(quote);
+            let mut element = XMLElement::new("a");
+            element.add_attribute("x", "'");
+            element.add_attribute("y", "\"");
+            element.add_attribute("z", "");
+            let mut output = Vec::new();
+            element.write_with(&mut output, options).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(
+            write(AttributeQuote::Double),
+            r#"<a x="'" y="&quot;" z=""/>"#
+        );
+        assert_eq!(
+            write(AttributeQuote::Single),
+            r#"<a x='&apos;' y='"' z=''/>"#
+        );
+        assert_eq!(write(AttributeQuote::Auto), r#"<a x="'" y='"' z=""/>"#);
+    }
+
+    #[test]
+    fn prolog_and_comments() {
+        let mut writer = XMLWriter::new(Vec::new());
+        writer.comment(" generated ").unwrap();
+        writer
+            .processing_instruction("xml-stylesheet", "href=\"s.xsl\"")
+            .unwrap();
+        writer.start_element("root").unwrap();
+        writer.comment(" first ").unwrap();
+        writer.start_element("a").unwrap();
+        writer.text("x").unwrap();
+        writer.comment("inline").unwrap();
+        writer.end_element("a").unwrap();
+        assert!(matches!(
+            writer.comment("a--b"),
+            Err(XMLError::InvalidComment { .. })
+        ));
+        assert!(writer.comment("a-").is_err());
+        writer.end_element("root").unwrap();
+        writer.comment("end").unwrap();
+        let output = writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!-- generated -->\n<?xml-stylesheet href=\"s.xsl\"?>\n<root>\n\t<!-- first -->\n\t<a>x<!--inline--></a>\n</root>\n<!--end-->\n"
+        );
+    }
+
+    #[test]
+    fn doctype() {
+        let mut doctype = XMLDoctype::new("doc").system_id("doc's.dtd");
+        doctype.add_entity("name", "say \"hi\"");
+        doctype.add_raw("<!ELEMENT doc (#PCDATA)>");
+
+        let mut writer = XMLWriter::new(Vec::new());
+        writer.comment("c").unwrap();
+        writer.doctype(&doctype).unwrap();
+        assert!(matches!(
+            writer.doctype(&doctype),
+            Err(XMLError::MisplacedDoctype)
+        ));
+        writer.write_element(&XMLElement::new("doc")).unwrap();
+        let output = writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!--c-->\n<!DOCTYPE doc SYSTEM \"doc's.dtd\" [\n\t<!ENTITY name \"say &#34;hi&#34;\">\n\t<!ELEMENT doc (#PCDATA)>\n]>\n<doc />\n"
+        );
+
+        let doctype = XMLDoctype::new("html").public_id("-//W3C//DTD \"XHTML\"//EN", "x.dtd");
+        let mut writer = XMLWriter::new(Vec::new());
+        assert!(matches!(
+            writer.doctype(&doctype),
+            Err(XMLError::InvalidDoctype { .. })
+        ));
+
+        let mut doctype = XMLDoctype::new("doc");
+        doctype.add_entity("ok", "&amp; &#38;#38; &#x26; &other;");
+        assert!(XMLWriter::new(Vec::new()).doctype(&doctype).is_ok());
+        let mut doctype = XMLDoctype::new("doc");
+        doctype.add_raw("<!-- ]> --><!ENTITY % e 'x>'> %e; <?pi ]>?>");
+        assert!(XMLWriter::new(Vec::new()).doctype(&doctype).is_ok());
+        for raw in &["]>", "<!ELEMENT a", "<!-- a", "text", "<!ELEMENT a \u{1}>"] {
+            let mut doctype = XMLDoctype::new("doc");
+            doctype.add_raw(raw);
+            assert!(
+                XMLWriter::new(Vec::new()).doctype(&doctype).is_err(),
+                "{}",
+                raw
+            );
+        }
+        for value in &["AT&T", "&;", "&#;", "&#x;", "&#12a;", "&a b;"] {
+            let mut doctype = XMLDoctype::new("doc");
+            doctype.add_entity("bad", value);
+            assert!(
+                XMLWriter::new(Vec::new()).doctype(&doctype).is_err(),
+                "{}",
+                value
+            );
+        }
+    }
+}